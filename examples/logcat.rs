@@ -1,7 +1,7 @@
 use anyhow::Result;
 use async_stream::stream;
 use async_trait::async_trait;
-use futures::{Sink, Stream, StreamExt};
+use futures::{Stream, StreamExt};
 use regex::Regex;
 use std::collections::HashMap;
 use std::error::Error;
@@ -10,6 +10,7 @@ use std::pin::Pin;
 use tokio::io::AsyncBufReadExt;
 use tokio::process::{Child, Command};
 
+#[allow(dead_code)]
 #[derive(Debug, Clone)]
 pub(crate) struct Log {
     pub date: String,
@@ -64,7 +65,6 @@ impl Source for AdbSource {
 
         let s = stream! {
             let mut line = String::new();
-            let mut buffer = String::new();
             let mut map = HashMap::new();
 
             while let Ok(bytes_read) = reader.read_line(&mut line).await {
@@ -73,9 +73,8 @@ impl Source for AdbSource {
                 }
 
                 if line.starts_with("---------") {
-                    let spl = line.trim().split_whitespace().collect::<Vec<&str>>();
-                    buffer = spl[3].to_string();
-                    map.insert("buffer", buffer.clone());
+                    let spl = line.split_whitespace().collect::<Vec<&str>>();
+                    map.insert("buffer", spl[3].to_string());
                 } else if line.trim().is_empty() {
                     if map.contains_key("message") {
                         let log = Log {
@@ -123,11 +122,8 @@ async fn main() {
     tokio::spawn(async move {
         let mut logs = adb.source().await;
         while let Some(r) = logs.next().await {
-            match r {
-                Ok(log) => {
-                    println!("{}", log);
-                }
-                Err(_) => {}
+            if let Ok(log) = r {
+                println!("{}", log);
             }
         }
     })
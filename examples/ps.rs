@@ -14,7 +14,7 @@ async fn main() {
             if bytes_read == 0 {
                 break;
             }
-            let spl = line.trim().split_whitespace().collect::<Vec<&str>>();
+            let spl = line.split_whitespace().collect::<Vec<&str>>();
             let name = spl[8];
             let pid = spl[1];
             for p in pids.iter() {
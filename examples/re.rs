@@ -22,16 +22,16 @@ fn main() {
     for s in it {
         if let Some(cap) = re.captures(s) {
             println!("{}---->{};", s, re.is_match(s));
-            println!(">>date:{};", cap.get(1).unwrap().as_str().to_string());
-            println!(">>time:{};", cap.get(2).unwrap().as_str().to_string());
-            println!(">>pid:{};", cap.get(3).unwrap().as_str().to_string());
+            println!(">>date:{};", cap.get(1).unwrap().as_str());
+            println!(">>time:{};", cap.get(2).unwrap().as_str());
+            println!(">>pid:{};", cap.get(3).unwrap().as_str());
             let content = cap.get(4).unwrap().as_str().trim_start().to_string();
             let content = content.splitn(2, ' ').collect::<Vec<&str>>();
             println!(">>tid:{};", content[0].to_string().trim());
             let content = content[1].to_string();
             let content = content.splitn(2, '/').collect::<Vec<&str>>();
-            println!("==>>level:{};", content[0].to_string());
-            println!("==>>tag:{};", content[1].to_string());
+            println!("==>>level:{};", content[0]);
+            println!("==>>tag:{};", content[1]);
         } else {
             println!("{}---->{}", s, re.is_match(s));
         }
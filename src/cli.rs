@@ -1,7 +1,14 @@
+use crate::gate::Condition;
 use crate::log::Level;
+use crate::retrace::Mapping;
+use crate::sink::{Highlight, Template, Theme};
+use crate::source::Since;
 use anyhow::Result;
 use clap::{value_parser, Arg, ArgAction, ArgMatches, Command};
-use std::path::PathBuf;
+use regex::Regex;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
 
 #[derive(Debug, Clone)]
 pub(crate) struct Cli {
@@ -9,13 +16,43 @@ pub(crate) struct Cli {
     pub tag_width: usize,
     pub revert: String,
     pub clear: bool,
+    pub follow: bool,
     pub level: Level,
     pub color: String,
+    pub theme: Theme,
+    pub color_by: String,
+    pub format: Option<Template>,
+    pub process_names: bool,
+    pub thread_names: bool,
+    pub time: String,
+    pub marker: Option<Regex>,
+    pub highlights: Vec<Highlight>,
     pub output: Option<PathBuf>,
     pub process: Vec<String>,
     pub buffers: Vec<String>,
     pub device: String,
     pub ignore: bool,
+    pub dump: bool,
+    pub since: Option<Since>,
+    pub launch: Option<String>,
+    pub force_stop: bool,
+    pub current: bool,
+    pub follow_focus: bool,
+    pub tui: bool,
+    pub pager: bool,
+    pub crash_dir: Option<PathBuf>,
+    pub pull_anr: bool,
+    pub mapping: Option<Arc<Mapping>>,
+    pub symbols: Option<PathBuf>,
+    pub until: Option<Regex>,
+    pub fail_on: Vec<Condition>,
+    pub timeout: Option<Duration>,
+    pub before: usize,
+    pub after: usize,
+    pub join: Option<Duration>,
+    pub pretty: Option<String>,
+    pub dedup: bool,
+    pub rate_limit: Option<usize>,
 }
 
 pub(crate) fn cli() -> Result<Cli> {
@@ -41,29 +78,120 @@ pub(crate) fn cli() -> Result<Cli> {
 
     let output = matches.get_one::<PathBuf>("output");
     let color = matches.get_one::<String>("color").unwrap().to_owned();
+    let theme = matches.get_one::<Theme>("theme").unwrap().to_owned();
+    let color_by = matches.get_one::<String>("color_by").unwrap().to_owned();
+    let format = matches.get_one::<Template>("format").cloned();
+    let process_names = matches.get_flag("process_names");
+    let thread_names = matches.get_flag("thread_names");
+    let time = matches.get_one::<String>("time").unwrap().to_owned();
+    let marker = matches.get_one::<Regex>("marker").cloned();
     let process = get_many(&matches, "process");
     let buffers = get_many(&matches, "buffer");
     let clear = matches.get_flag("clear");
+    let follow = matches.get_flag("follow");
     let level = matches.get_one::<Level>("level").unwrap().to_owned();
     let ignore = matches.get_flag("ignore");
+    let highlights = matches
+        .get_many::<Highlight>("highlight")
+        .unwrap_or_default()
+        .map(|h| {
+            if ignore {
+                h.clone().case_insensitive()
+            } else {
+                h.clone()
+            }
+        })
+        .collect::<Vec<Highlight>>();
     let device = match matches.get_one::<String>("device") {
         None => "",
         Some(s) => s,
     }
     .to_owned();
+    let dump = matches.get_flag("dump");
+    let launch = matches.get_one::<String>("launch").cloned();
+    let force_stop = matches.get_flag("force_stop");
+    let current = matches.get_flag("current");
+    let follow_focus = matches.get_flag("follow_focus");
+    let join = matches
+        .get_one::<u64>("join")
+        .map(|ms| Duration::from_millis(*ms));
+    let pretty = matches.get_one::<String>("pretty").cloned();
+    let dedup = matches.get_flag("dedup");
+    let rate_limit = matches.get_one::<usize>("rate_limit").copied();
+    let tui = matches.get_flag("tui");
+    let pager = matches.get_flag("pager");
+    let crash_dir = matches.get_one::<PathBuf>("crash_dir").cloned();
+    let pull_anr = matches.get_flag("pull_anr");
+    let mapping = matches.get_one::<Arc<Mapping>>("mapping").cloned();
+    let symbols = matches.get_one::<PathBuf>("symbols").cloned();
+    let until = matches.get_one::<Regex>("until").cloned();
+    let fail_on = matches
+        .get_many::<Condition>("fail_on")
+        .unwrap_or_default()
+        .cloned()
+        .collect::<Vec<Condition>>();
+    let timeout = matches.get_one::<Duration>("timeout").copied();
+    // Like grep, -A and -B win over -C
+    let around = matches.get_one::<usize>("context").copied().unwrap_or(0);
+    let before = matches
+        .get_one::<usize>("before_context")
+        .copied()
+        .unwrap_or(around);
+    let after = matches
+        .get_one::<usize>("after_context")
+        .copied()
+        .unwrap_or(around);
+    let since = match (
+        matches.get_one::<usize>("tail"),
+        matches.get_one::<String>("since"),
+    ) {
+        (Some(count), _) => Some(Since::Count(*count)),
+        (None, Some(time)) => Some(Since::Time(time.to_owned())),
+        (None, None) => None,
+    };
 
     Ok(Cli {
         tag,
         tag_width,
         revert,
         color,
+        theme,
+        color_by,
+        format,
+        process_names,
+        thread_names,
+        time,
+        marker,
+        highlights,
         clear,
+        follow,
         output: output.cloned(),
         level,
         process,
         buffers,
         device,
         ignore,
+        dump,
+        since,
+        launch,
+        force_stop,
+        current,
+        follow_focus,
+        tui,
+        pager,
+        crash_dir,
+        pull_anr,
+        mapping,
+        symbols,
+        until,
+        fail_on,
+        timeout,
+        before,
+        after,
+        join,
+        pretty,
+        dedup,
+        rate_limit,
     })
 }
 
@@ -95,6 +223,30 @@ fn get_arg_matches() -> ArgMatches {
                 .help("Selected lines are those not matching any of the specified patterns.")
                 .conflicts_with("tag")
         )
+        .arg(
+            Arg::new("after_context")
+                .short('A')
+                .long("after-context")
+                .help("Also show the N records of the process that follow a match of the level, tag and revert filters")
+                .conflicts_with("tui")
+                .value_parser(value_parser!(usize))
+        )
+        .arg(
+            Arg::new("before_context")
+                .short('B')
+                .long("before-context")
+                .help("Also show the N records of the process that lead up to a match")
+                .conflicts_with("tui")
+                .value_parser(value_parser!(usize))
+        )
+        .arg(
+            Arg::new("context")
+                .short('C')
+                .long("context")
+                .help("Also show N records of the process before and after a match")
+                .conflicts_with("tui")
+                .value_parser(value_parser!(usize))
+        )
         .arg(
             Arg::new("buffer")
                 .short('b')
@@ -108,7 +260,14 @@ fn get_arg_matches() -> ArgMatches {
             Arg::new("clear")
                 .short('c')
                 .long("clear")
-                .help("Clear (flush) the entire log and exit")
+                .help("Clear (flush) the selected buffers and exit")
+                .action(ArgAction::SetTrue)
+        )
+        .arg(
+            Arg::new("follow")
+                .long("follow")
+                .help("Keep following the log after --clear")
+                .requires("clear")
                 .action(ArgAction::SetTrue)
         )
         .arg(
@@ -130,10 +289,149 @@ fn get_arg_matches() -> ArgMatches {
         .arg(
             Arg::new("color")
                 .long("color")
-                .help("Display in highlighted color to match priority")
+                .help("Display in highlighted color to match priority, auto follows the terminal, NO_COLOR and CLICOLOR_FORCE")
                 .default_value("auto")
                 .value_parser(["auto", "always", "never"])
         )
+        .arg(
+            Arg::new("theme")
+                .long("theme")
+                .help("Color theme, one of dark, light, solarized or the path of a theme file")
+                .default_value("dark")
+                .value_parser(|s: &str| Theme::load(s).map_err(|e| e.to_string()))
+        )
+        .arg(
+            Arg::new("color_by")
+                .long("color-by")
+                .help("Draw the tag column, the message, or both in a stable color per tag instead of the level color")
+                .default_value("tag")
+                .value_parser(["level", "tag", "message", "both"])
+        )
+        .arg(
+            Arg::new("format")
+                .long("format")
+                .help("Line template for the terminal and the output file, e.g. '{time} {pid:>5} {tag:20.20} {level} {msg}'. Fields are date, time, pid, tid, level, tag, msg, buffer, process, thread and device, '{name?}' is left out when empty")
+                .value_parser(|s: &str| Template::parse(s).map_err(|e| e.to_string()))
+        )
+        .arg(
+            Arg::new("process_names")
+                .long("process-names")
+                .help("Show the process name in place of the pid")
+                .conflicts_with("format")
+                .action(ArgAction::SetTrue)
+        )
+        .arg(
+            Arg::new("thread_names")
+                .long("thread-names")
+                .help("Show the thread name in place of the tid")
+                .conflicts_with("format")
+                .action(ArgAction::SetTrue)
+        )
+        .arg(
+            Arg::new("time")
+                .long("time")
+                .help("Show the device time, the time since the first line, since the previous line, since the last --marker line, or the device time on the host clock")
+                .default_value("device")
+                .value_parser(["device", "relative", "delta", "marker", "local"])
+                .conflicts_with("tui")
+        )
+        .arg(
+            Arg::new("marker")
+                .long("marker")
+                .help("The pattern of lines --time marker counts from")
+                .required_if_eq("time", "marker")
+                .value_parser(|s: &str| Regex::new(s).map_err(|e| e.to_string()))
+        )
+        .arg(
+            Arg::new("highlight")
+                .long("highlight")
+                .help("Highlight matches in tags and messages, '#rrggbb:pattern' picks the color. Can be repeated")
+                .value_parser(|s: &str| Highlight::parse(s).map_err(|e| e.to_string()))
+                .action(ArgAction::Append)
+                .conflicts_with("tui")
+        )
+        .arg(
+            Arg::new("pretty")
+                .long("pretty")
+                .value_name("kind")
+                .help("Lay out JSON payloads in messages indented and colored over several lines, 'xml' does XML ones and 'all' both")
+                .conflicts_with("tui")
+                .num_args(0..=1)
+                .require_equals(true)
+                .default_missing_value("json")
+                .value_parser(["json", "xml", "all"])
+        )
+        .arg(
+            Arg::new("join")
+                .long("join")
+                .value_name("ms")
+                .help("Join records of the same pid, tid, tag and level logged within ms of each other, like a payload split at the size limit. Events and crash records are kept apart")
+                .num_args(0..=1)
+                .require_equals(true)
+                .default_missing_value("20")
+                .value_parser(value_parser!(u64))
+        )
+        .arg(
+            Arg::new("dedup")
+                .long("dedup")
+                .help("Collapse records repeating the one before, numbers and hex aside, into a 'repeated N times' line")
+                .conflicts_with("tui")
+                .action(ArgAction::SetTrue)
+        )
+        .arg(
+            Arg::new("rate_limit")
+                .long("rate-limit")
+                .help("Show at most N records per tag each second, and how many were suppressed")
+                .conflicts_with("tui")
+                .value_parser(value_parser!(usize))
+        )
+        .arg(
+            Arg::new("crash_dir")
+                .long("crash-dir")
+                .help("Save each crash of the followed processes to its own file in the directory")
+                .value_parser(value_parser!(PathBuf))
+        )
+        .arg(
+            Arg::new("pull_anr")
+                .long("pull-anr")
+                .help("Pull the traces of an ANR from /data/anr, or dumpsys activity lastanr, into --crash-dir")
+                .requires("crash_dir")
+                .action(ArgAction::SetTrue)
+        )
+        .arg(
+            Arg::new("mapping")
+                .long("mapping")
+                .help("Retrace obfuscated stack traces with a ProGuard or R8 mapping file")
+                .value_parser(|s: &str| Mapping::load(Path::new(s)).map(Arc::new).map_err(|e| e.to_string()))
+        )
+        .arg(
+            Arg::new("symbols")
+                .long("symbols")
+                .help("Symbolize native backtraces with the unstripped libraries in the directory, matched by build id or name")
+                .value_parser(value_parser!(PathBuf))
+        )
+        .arg(
+            Arg::new("until")
+                .long("until")
+                .help("Exit 0 once a shown record's message matches the pattern, 4 when the log ends first")
+                .conflicts_with("tui")
+                .value_parser(|s: &str| Regex::new(s).map_err(|e| e.to_string()))
+        )
+        .arg(
+            Arg::new("fail_on")
+                .long("fail-on")
+                .help("Exit 3 once a shown record's message matches the pattern, or 'crash' on a crash of a followed process. Can be repeated")
+                .conflicts_with("tui")
+                .value_parser(Condition::parse)
+                .action(ArgAction::Append)
+        )
+        .arg(
+            Arg::new("timeout")
+                .long("timeout")
+                .help("Stop after the duration, like 30s, 5m or 500ms, and exit 124 when --until has not matched")
+                .conflicts_with("tui")
+                .value_parser(parse_duration)
+        )
         .arg(
             Arg::new("ignore")
                 .short('i')
@@ -141,6 +439,67 @@ fn get_arg_matches() -> ArgMatches {
                 .help("Ignore case")
                 .action(ArgAction::SetTrue)
         )
+        .arg(
+            Arg::new("dump")
+                .short('d')
+                .long("dump")
+                .help("Dump the log and exit instead of following it")
+                .action(ArgAction::SetTrue)
+        )
+        .arg(
+            Arg::new("tail")
+                .long("tail")
+                .help("Start with the most recent <tail> lines")
+                .value_parser(value_parser!(usize))
+                .conflicts_with("since")
+        )
+        .arg(
+            Arg::new("since")
+                .long("since")
+                .help("Start with the lines since the time, 'MM-DD hh:mm:ss.mmm' or 'YYYY-MM-DD hh:mm:ss.mmm'")
+                .value_parser(parse_since)
+        )
+        .arg(
+            Arg::new("launch")
+                .long("launch")
+                .help("Start the launcher activity of the package and follow its logs")
+                .conflicts_with("dump")
+        )
+        .arg(
+            Arg::new("force_stop")
+                .long("force-stop")
+                .help("Force stop the package before --launch")
+                .requires("launch")
+                .action(ArgAction::SetTrue)
+        )
+        .arg(
+            Arg::new("current")
+                .long("current")
+                .help("Filter the app currently in the foreground")
+                .conflicts_with_all(["process", "launch"])
+                .action(ArgAction::SetTrue)
+        )
+        .arg(
+            Arg::new("follow_focus")
+                .long("follow-focus")
+                .help("Switch to whichever app comes to the foreground")
+                .conflicts_with_all(["process", "launch"])
+                .action(ArgAction::SetTrue)
+        )
+        .arg(
+            Arg::new("tui")
+                .long("tui")
+                .help("Browse the log full screen, with scrollback, pause, search and filters that can be edited while it runs")
+                .conflicts_with("output")
+                .action(ArgAction::SetTrue)
+        )
+        .arg(
+            Arg::new("pager")
+                .long("pager")
+                .help("Page the output with $PAGER, or 'less -R' when unset")
+                .conflicts_with("tui")
+                .action(ArgAction::SetTrue)
+        )
         .arg(
             Arg::new("device")
                 .required(false)
@@ -156,11 +515,53 @@ fn get_arg_matches() -> ArgMatches {
         .get_matches()
 }
 
-fn get_many<'a>(matches: &ArgMatches, arg: &str) -> Vec<String> {
+fn parse_since(s: &str) -> Result<String, String> {
+    let re = Regex::new(r"^(\d{4}-)?\d{2}-\d{2} \d{2}:\d{2}:\d{2}(\.\d{3})?$").unwrap();
+    if re.is_match(s) {
+        Ok(s.to_owned())
+    } else {
+        Err("expected 'MM-DD hh:mm:ss.mmm'".to_owned())
+    }
+}
+
+fn parse_duration(s: &str) -> Result<Duration, String> {
+    let re = Regex::new(r"^(\d+)(ms|s|m|h)?$").unwrap();
+    let cap = re
+        .captures(s)
+        .ok_or_else(|| "expected a duration like 30s, 5m or 500ms".to_owned())?;
+    let n = cap[1].parse::<u64>().map_err(|e| e.to_string())?;
+    let secs = |unit: u64| {
+        n.checked_mul(unit)
+            .map(Duration::from_secs)
+            .ok_or_else(|| format!("{} is too long", s))
+    };
+    match cap.get(2).map(|m| m.as_str()) {
+        Some("ms") => Ok(Duration::from_millis(n)),
+        Some("m") => secs(60),
+        Some("h") => secs(3600),
+        _ => secs(1),
+    }
+}
+
+fn get_many(matches: &ArgMatches, arg: &str) -> Vec<String> {
     matches
         .get_many::<String>(arg)
         .unwrap_or_default()
-        .into_iter()
         .map(|v| v.to_owned())
         .collect::<Vec<String>>()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_durations() {
+        assert_eq!(parse_duration("500ms"), Ok(Duration::from_millis(500)));
+        assert_eq!(parse_duration("30"), Ok(Duration::from_secs(30)));
+        assert_eq!(parse_duration("5m"), Ok(Duration::from_secs(300)));
+        assert_eq!(parse_duration("2h"), Ok(Duration::from_secs(7200)));
+        assert!(parse_duration("5d").is_err());
+        assert!(parse_duration(&format!("{}h", u64::MAX / 60)).is_err());
+    }
+}
@@ -0,0 +1,86 @@
+use crate::log::Log;
+use std::collections::{HashMap, VecDeque};
+
+///
+/// What to write ahead of a matching record
+///
+pub(crate) enum Line {
+    Context(Log),
+    /// Between groups that are not next to each other
+    Separator,
+}
+
+#[derive(Default)]
+struct Process {
+    /// The latest hidden records, for the next match
+    before: VecDeque<Log>,
+    /// Hidden records still to show after the last match
+    after: usize,
+    /// Some record went unshown since the last one written
+    skipped: bool,
+    written: bool,
+}
+
+///
+/// grep's -A/-B/-C for records, the records of a process the level, tag and revert
+/// filters dropped are shown around the ones they let through
+///
+pub(crate) struct Context {
+    before: usize,
+    after: usize,
+    /// By pid, context never crosses from one process to another
+    processes: HashMap<String, Process>,
+}
+
+impl Context {
+    pub fn new(before: usize, after: usize) -> Self {
+        Self {
+            before,
+            after,
+            processes: HashMap::new(),
+        }
+    }
+
+    ///
+    /// A record the filters let through, returns what goes ahead of it
+    ///
+    pub fn matched(&mut self, log: &Log) -> Vec<Line> {
+        if self.before == 0 && self.after == 0 {
+            return Vec::new();
+        }
+        let process = self.processes.entry(log.pid.clone()).or_default();
+        let mut lines = Vec::new();
+        if process.skipped && process.written {
+            lines.push(Line::Separator);
+        }
+        lines.extend(process.before.drain(..).map(Line::Context));
+        process.after = self.after;
+        process.skipped = false;
+        process.written = true;
+        lines
+    }
+
+    ///
+    /// A record the filters dropped, returned when it is shown after a match
+    ///
+    pub fn hidden(&mut self, log: &Log) -> Option<Log> {
+        if self.before == 0 && self.after == 0 {
+            return None;
+        }
+        let process = self.processes.entry(log.pid.clone()).or_default();
+        if process.after > 0 {
+            process.after -= 1;
+            return Some(log.clone());
+        }
+        if self.before == 0 {
+            process.skipped = true;
+            return None;
+        }
+        process.before.push_back(log.clone());
+        if process.before.len() > self.before {
+            process.before.pop_front();
+            process.skipped = true;
+        }
+        None
+    }
+}
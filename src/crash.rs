@@ -0,0 +1,410 @@
+use lazy_static::lazy_static;
+use regex::Regex;
+use std::fmt::{Display, Formatter};
+
+/// Frames of each exception kept in the summary
+const SUMMARY_FRAMES: usize = 3;
+/// Frames of a native backtrace kept in the summary
+const SUMMARY_NATIVE_FRAMES: usize = 6;
+
+lazy_static! {
+    static ref PROCESS: Regex = Regex::new(r"^pid: (\d+), tid: (\d+), name: (.*?)\s+>>> (.+?) <<<").unwrap();
+    static ref SIGNAL: Regex = Regex::new(
+        r"^signal (\d+) \((\w+)\)(?:, code (-?\d+) \(([^)]*)\))?, fault addr (\S+)"
+    )
+    .unwrap();
+    static ref FATAL_SIGNAL: Regex = Regex::new(
+        r"^Fatal signal (\d+) \((\w+)\)(?:, code (-?\d+) \(([^)]*)\))?(?:, fault addr (\S+))? in tid (\d+) \((.*?)\), pid (\d+) \((.*)\)"
+    )
+    .unwrap();
+    static ref FRAME: Regex = Regex::new(r"^#(\d+) pc ([0-9a-fA-F]+)\s+(\S+)(.*)$").unwrap();
+    static ref BUILD_ID: Regex = Regex::new(r"\s*\(BuildId: ([0-9a-fA-F]+)\)\s*$").unwrap();
+    static ref OFFSET: Regex = Regex::new(r"^\s*\(offset (0x[0-9a-fA-F]+)\)").unwrap();
+    static ref ANR_IN: Regex = Regex::new(r"^ANR in (\S+)(?: \((.*)\))?").unwrap();
+}
+
+///
+/// An exception of a Java crash, the thrown one or one of its causes
+///
+#[derive(Debug, Clone)]
+pub struct JavaException {
+    /// Like 'java.lang.IllegalStateException: boom'
+    pub header: String,
+    /// The 'at ...' lines, innermost call first
+    pub frames: Vec<String>,
+    /// A 'Caused by:' or 'Suppressed:' entry rather than the thrown exception
+    pub cause: bool,
+}
+
+///
+/// An uncaught Java or Kotlin exception, gathered from the `AndroidRuntime` records
+///
+#[derive(Debug, Clone)]
+pub struct JavaCrash {
+    pub pid: String,
+    /// The process name
+    pub name: String,
+    pub thread: String,
+    pub date: String,
+    pub time: String,
+    /// The thrown exception and its causes, outermost first
+    pub exceptions: Vec<JavaException>,
+    /// The trace as logged
+    pub lines: Vec<String>,
+}
+
+#[allow(dead_code)]
+impl JavaCrash {
+    ///
+    /// Read the trace `lines` logged by `pid`, starting at 'FATAL EXCEPTION'
+    ///
+    pub(crate) fn parse(pid: &str, date: &str, time: &str, lines: Vec<String>) -> Self {
+        let mut crash = Self {
+            pid: pid.to_string(),
+            name: String::new(),
+            thread: String::new(),
+            date: date.to_string(),
+            time: time.to_string(),
+            exceptions: Vec::new(),
+            lines,
+        };
+        for line in &crash.lines {
+            let line = line.trim();
+            if let Some(thread) = line.strip_prefix("FATAL EXCEPTION:") {
+                crash.thread = thread.trim().to_string();
+            } else if let Some(process) = line.strip_prefix("Process:") {
+                // 'Process: com.foo, PID: 1234'
+                let name = process.split(',').next().unwrap_or_default();
+                crash.name = name.trim().to_string();
+            } else if let Some(frame) = line.strip_prefix("at ") {
+                if let Some(e) = crash.exceptions.last_mut() {
+                    e.frames.push(frame.to_string());
+                }
+            } else if line.starts_with("...") && line.ends_with("more") {
+                continue;
+            } else if let Some(cause) = line.strip_prefix("Caused by:") {
+                crash.exceptions.push(JavaException {
+                    header: cause.trim().to_string(),
+                    frames: Vec::new(),
+                    cause: true,
+                });
+            } else if line.starts_with("Suppressed:") {
+                crash.exceptions.push(JavaException {
+                    header: line.to_string(),
+                    frames: Vec::new(),
+                    cause: true,
+                });
+            } else if crash.exceptions.is_empty() && !line.is_empty() {
+                crash.exceptions.push(JavaException {
+                    header: line.to_string(),
+                    frames: Vec::new(),
+                    cause: false,
+                });
+            }
+        }
+        crash
+    }
+
+    ///
+    /// A few lines per exception, for a crash block under the banner
+    ///
+    pub fn summary(&self) -> Vec<String> {
+        let mut lines = Vec::new();
+        for e in &self.exceptions {
+            if e.cause && !e.header.starts_with("Suppressed:") {
+                lines.push(format!("Caused by: {}", e.header));
+            } else {
+                lines.push(e.header.clone());
+            }
+            for frame in e.frames.iter().take(SUMMARY_FRAMES) {
+                lines.push(format!("    at {}", frame));
+            }
+            if e.frames.len() > SUMMARY_FRAMES {
+                lines.push(format!("    ... {} more", e.frames.len() - SUMMARY_FRAMES));
+            }
+        }
+        lines
+    }
+}
+
+impl Display for JavaCrash {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Process {} (pid {}) crashed", self.name, self.pid)?;
+        if !self.thread.is_empty() {
+            write!(f, " on thread {}", self.thread)?;
+        }
+        Ok(())
+    }
+}
+
+///
+/// A frame of a native backtrace, like
+/// `#01 pc 0000000000012345  /data/app/.../libfoo.so (foo::bar()+20) (BuildId: 1a2b)`
+///
+#[allow(dead_code)]
+#[derive(Debug, Clone)]
+pub struct NativeFrame {
+    pub index: usize,
+    /// The pc relative to the start of the module
+    pub pc: u64,
+    pub module: String,
+    /// The symbol and the offset into it as logged, like 'abort+164'
+    pub symbol: Option<String>,
+    pub build_id: Option<String>,
+}
+
+impl NativeFrame {
+    pub(crate) fn parse(line: &str) -> Option<Self> {
+        let cap = FRAME.captures(line.trim())?;
+        let mut rest = cap[4].to_string();
+        let build_id = BUILD_ID.captures(&rest).map(|c| c[1].to_string());
+        rest = BUILD_ID.replace(&rest, "").into_owned();
+        // The offset of the module in an apk it is mapped from
+        rest = OFFSET.replace(&rest, "").into_owned();
+        let rest = rest.trim();
+        let symbol = rest
+            .strip_prefix('(')
+            .and_then(|s| s.strip_suffix(')'))
+            .map(String::from);
+        Some(Self {
+            index: cap[1].parse().ok()?,
+            pc: u64::from_str_radix(&cap[2], 16).ok()?,
+            module: cap[3].to_string(),
+            symbol,
+            build_id,
+        })
+    }
+}
+
+impl Display for NativeFrame {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "#{:02} pc {:016x}  {}", self.index, self.pc, self.module)?;
+        if let Some(symbol) = &self.symbol {
+            write!(f, " ({})", symbol)?;
+        }
+        Ok(())
+    }
+}
+
+///
+/// A native crash, gathered from the tombstone `crash_dump` logs under the `DEBUG` tag,
+/// or from the crashing process's own 'Fatal signal' line when no tombstone follows
+///
+#[derive(Debug, Clone)]
+pub struct NativeCrash {
+    /// The crashed process, not the crash_dump that logged it
+    pub pid: String,
+    pub name: String,
+    pub tid: String,
+    pub thread: String,
+    pub date: String,
+    pub time: String,
+    /// Like 'SIGSEGV'
+    pub signal: String,
+    /// Like 'SEGV_MAPERR'
+    pub code: String,
+    pub fault_addr: String,
+    /// What debuggerd guessed, like 'null pointer dereference'
+    pub cause: Option<String>,
+    pub abort_message: Option<String>,
+    pub frames: Vec<NativeFrame>,
+    /// The tombstone as logged
+    pub lines: Vec<String>,
+}
+
+#[allow(dead_code)]
+impl NativeCrash {
+    ///
+    /// Read the tombstone `lines`, starting at the '*** *** ***' header
+    ///
+    pub(crate) fn parse(date: &str, time: &str, lines: Vec<String>) -> Self {
+        let mut crash = Self {
+            pid: String::new(),
+            name: String::new(),
+            tid: String::new(),
+            thread: String::new(),
+            date: date.to_string(),
+            time: time.to_string(),
+            signal: String::new(),
+            code: String::new(),
+            fault_addr: String::new(),
+            cause: None,
+            abort_message: None,
+            frames: Vec::new(),
+            lines,
+        };
+        for line in &crash.lines {
+            let line = line.trim();
+            if let Some(cap) = PROCESS.captures(line) {
+                crash.pid = cap[1].to_string();
+                crash.tid = cap[2].to_string();
+                crash.thread = cap[3].to_string();
+                crash.name = cap[4].to_string();
+            } else if let Some(cap) = SIGNAL.captures(line) {
+                crash.signal = cap[2].to_string();
+                crash.code = cap.get(4).map_or("", |m| m.as_str()).to_string();
+                crash.fault_addr = cap[5].to_string();
+            } else if let Some(cause) = line.strip_prefix("Cause:") {
+                crash.cause = Some(cause.trim().to_string());
+            } else if let Some(message) = line.strip_prefix("Abort message:") {
+                let message = message.trim();
+                let message = message
+                    .strip_prefix('\'')
+                    .and_then(|m| m.strip_suffix('\''))
+                    .unwrap_or(message);
+                crash.abort_message = Some(message.to_string());
+            } else if let Some(frame) = NativeFrame::parse(line) {
+                crash.frames.push(frame);
+            }
+        }
+        crash
+    }
+
+    ///
+    /// Read a `libc` 'Fatal signal 11 (SIGSEGV), code 1 (SEGV_MAPERR), fault addr 0x0
+    /// in tid 1250 (RenderThread), pid 1234 (com.foo)' line
+    ///
+    pub(crate) fn parse_fatal_signal(date: &str, time: &str, line: &str) -> Option<Self> {
+        let cap = FATAL_SIGNAL.captures(line.trim())?;
+        Some(Self {
+            pid: cap[8].to_string(),
+            name: cap[9].to_string(),
+            tid: cap[6].to_string(),
+            thread: cap[7].to_string(),
+            date: date.to_string(),
+            time: time.to_string(),
+            signal: cap[2].to_string(),
+            code: cap.get(4).map_or("", |m| m.as_str()).to_string(),
+            fault_addr: cap.get(5).map_or("", |m| m.as_str()).to_string(),
+            cause: None,
+            abort_message: None,
+            frames: Vec::new(),
+            lines: vec![line.to_string()],
+        })
+    }
+
+    ///
+    /// The signal, the reason and the top frames, for a crash block under the banner
+    ///
+    pub fn summary(&self) -> Vec<String> {
+        let mut lines = Vec::new();
+        let mut signal = self.signal.clone();
+        if !self.code.is_empty() {
+            signal.push_str(&format!(" ({})", self.code));
+        }
+        if !self.fault_addr.is_empty() {
+            signal.push_str(&format!(", fault addr {}", self.fault_addr));
+        }
+        lines.push(signal);
+        if let Some(cause) = &self.cause {
+            lines.push(format!("Cause: {}", cause));
+        }
+        if let Some(message) = &self.abort_message {
+            lines.push(format!("Abort message: '{}'", message));
+        }
+        for frame in self.frames.iter().take(SUMMARY_NATIVE_FRAMES) {
+            lines.push(format!("    {}", frame));
+        }
+        if self.frames.len() > SUMMARY_NATIVE_FRAMES {
+            lines.push(format!(
+                "    ... {} more",
+                self.frames.len() - SUMMARY_NATIVE_FRAMES
+            ));
+        }
+        lines
+    }
+}
+
+impl Display for NativeCrash {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Process {} (pid {}) crashed in native code",
+            self.name, self.pid
+        )?;
+        if !self.thread.is_empty() {
+            write!(f, " on thread {}", self.thread)?;
+        }
+        Ok(())
+    }
+}
+
+///
+/// An app not responding, gathered from the 'ANR in' report of `ActivityManager`
+///
+#[derive(Debug, Clone)]
+pub struct Anr {
+    pub pid: String,
+    pub name: String,
+    /// The component that timed out, like 'com.foo/.MainActivity'
+    pub component: Option<String>,
+    pub date: String,
+    pub time: String,
+    /// Like 'Input dispatching timed out (...)'
+    pub reason: String,
+    /// The 'CPU usage' header with the lines of the process and the total
+    pub cpu: Vec<String>,
+    /// The report as logged
+    pub lines: Vec<String>,
+}
+
+#[allow(dead_code)]
+impl Anr {
+    ///
+    /// Read the report `lines`, starting at 'ANR in com.foo'
+    ///
+    pub(crate) fn parse(date: &str, time: &str, lines: Vec<String>) -> Self {
+        let mut anr = Self {
+            pid: String::new(),
+            name: String::new(),
+            component: None,
+            date: date.to_string(),
+            time: time.to_string(),
+            reason: String::new(),
+            cpu: Vec::new(),
+            lines,
+        };
+        for line in &anr.lines {
+            let line = line.trim();
+            if let Some(cap) = ANR_IN.captures(line) {
+                anr.name = cap[1].to_string();
+                anr.component = cap.get(2).map(|m| m.as_str().to_string());
+            } else if let Some(pid) = line.strip_prefix("PID:") {
+                anr.pid = pid.trim().to_string();
+            } else if let Some(reason) = line.strip_prefix("Reason:") {
+                anr.reason = reason.trim().to_string();
+            } else if line.starts_with("CPU usage from")
+                || (!anr.cpu.is_empty()
+                    && (line.contains(&format!(" {}/", anr.pid)) || line.contains("TOTAL")))
+            {
+                anr.cpu.push(line.to_string());
+            }
+        }
+        anr
+    }
+
+    ///
+    /// The reason and the CPU usage, for a block under the banner
+    ///
+    pub fn summary(&self) -> Vec<String> {
+        let mut lines = Vec::new();
+        if !self.reason.is_empty() {
+            lines.push(format!("Reason: {}", self.reason));
+        }
+        if let Some(component) = &self.component {
+            lines.push(format!("Component: {}", component));
+        }
+        lines.extend(self.cpu.iter().cloned());
+        lines
+    }
+}
+
+impl Display for Anr {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Process {} (pid {}) is not responding",
+            self.name, self.pid
+        )
+    }
+}
@@ -0,0 +1,69 @@
+use crate::crash::Anr;
+use crate::detector::gather::{Gatherer, Report};
+use crate::detector::Detector;
+use crate::event::Event;
+use crate::filter::PidFilter;
+use crate::log::Log;
+use async_trait::async_trait;
+use crossbeam_channel::Sender;
+use std::sync::Arc;
+
+///
+/// Gathers the 'ANR in com.foo' report `ActivityManager` logs into one event.
+/// system_server logs plenty else meanwhile, so the report is gathered from the
+/// thread that started it.
+///
+pub struct AnrDetector {
+    /// Reports being logged, by the tid logging them
+    reports: Gatherer,
+    events: Sender<Event>,
+    pid_filter: Arc<PidFilter>,
+}
+
+impl AnrDetector {
+    #[allow(dead_code)]
+    pub fn new(events: Sender<Event>, pid_filter: Arc<PidFilter>) -> Self {
+        Self {
+            reports: Gatherer::new(),
+            events,
+            pid_filter,
+        }
+    }
+
+    fn send(&self, report: Report) {
+        let anr = Anr::parse(&report.date, &report.time, report.lines);
+        if !anr.name.is_empty() && self.pid_filter.follows(&anr.name) {
+            let _ = self.events.send(Event::Anr(anr));
+        }
+    }
+
+    fn is_report(log: &Log) -> bool {
+        log.tag == "ActivityManager" && log.level == "E"
+    }
+
+    ///
+    /// Whether the log ends the report thread `tid` is logging
+    ///
+    fn ends(log: &Log, tid: &str) -> bool {
+        log.tid == tid && (!Self::is_report(log) || log.message.starts_with("ANR in "))
+    }
+}
+
+#[async_trait]
+impl Detector for AnrDetector {
+    async fn detect(&self, log: &Log) {
+        for (_, report) in self.reports.ended(log, Self::ends) {
+            self.send(report);
+        }
+        if Self::is_report(log) {
+            let starts = log.message.starts_with("ANR in ");
+            self.reports.add(&log.tid, log, starts);
+        }
+    }
+
+    async fn finish(&self) {
+        for (_, report) in self.reports.finish() {
+            self.send(report);
+        }
+    }
+}
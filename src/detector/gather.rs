@@ -0,0 +1,142 @@
+use crate::log::Log;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// A report with no new lines for this long is complete
+const QUIET: Duration = Duration::from_secs(1);
+
+/// The lines of a report, dated by the record that started it
+pub(crate) struct Report {
+    pub date: String,
+    pub time: String,
+    pub lines: Vec<String>,
+}
+
+struct Pending {
+    report: Report,
+    last: Instant,
+}
+
+///
+/// Gathers reports spread over several records, like a crash, by a key such as
+/// the pid logging them. Other processes log in between, so each key gathers on
+/// its own until its detector says a record ends it, or it goes quiet.
+///
+pub(crate) struct Gatherer {
+    pending: Mutex<HashMap<String, Pending>>,
+}
+
+impl Gatherer {
+    pub fn new() -> Self {
+        Self {
+            pending: Mutex::new(HashMap::new()),
+        }
+    }
+
+    ///
+    /// Take the reports the log ends, by `ends(log, key)`, and those gone quiet
+    ///
+    pub fn ended(&self, log: &Log, ends: impl Fn(&Log, &str) -> bool) -> Vec<(String, Report)> {
+        let mut pending = self.pending.lock().unwrap();
+        let ended = pending
+            .iter()
+            .filter(|(key, p)| p.last.elapsed() > QUIET || ends(log, key))
+            .map(|(key, _)| key.clone())
+            .collect::<Vec<String>>();
+        ended
+            .into_iter()
+            .map(|key| {
+                let p = pending.remove(&key).unwrap();
+                (key, p.report)
+            })
+            .collect()
+    }
+
+    ///
+    /// Add the lines of the log to the report of `key`, starting a new one
+    /// when `starts`. Without a report started the log is left out.
+    ///
+    pub fn add(&self, key: &str, log: &Log, starts: bool) {
+        let mut pending = self.pending.lock().unwrap();
+        if starts {
+            pending.insert(
+                key.to_string(),
+                Pending {
+                    report: Report {
+                        date: log.date.clone(),
+                        time: log.time.clone(),
+                        lines: Vec::new(),
+                    },
+                    last: Instant::now(),
+                },
+            );
+        }
+        if let Some(p) = pending.get_mut(key) {
+            p.report.lines.extend(log.message.lines().map(String::from));
+            p.last = Instant::now();
+        }
+    }
+
+    /// Take every report still being gathered
+    pub fn finish(&self) -> Vec<(String, Report)> {
+        let pending = std::mem::take(&mut *self.pending.lock().unwrap());
+        pending
+            .into_iter()
+            .map(|(key, p)| (key, p.report))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn log(pid: &str, tag: &str, message: &str) -> Log {
+        Log {
+            date: "10-19".to_string(),
+            time: "12:00:00.000".to_string(),
+            pid: pid.to_string(),
+            tid: pid.to_string(),
+            level: "E".to_string(),
+            tag: tag.to_string(),
+            message: message.to_string(),
+            buffer: "crash".to_string(),
+        }
+    }
+
+    fn ends(log: &Log, pid: &str) -> bool {
+        log.pid == pid && log.tag != "Trace"
+    }
+
+    #[test]
+    fn gathers_each_key_on_its_own() {
+        let gatherer = Gatherer::new();
+        for l in [
+            log("1", "Trace", "first"),
+            log("2", "Trace", "other"),
+            log("1", "Trace", "at a\nat b"),
+            log("2", "Trace", "at c"),
+        ] {
+            assert!(gatherer.ended(&l, ends).is_empty());
+            gatherer.add(&l.pid, &l, l.message == "first" || l.message == "other");
+        }
+
+        let ended = gatherer.ended(&log("1", "Other", "done"), ends);
+        assert_eq!(ended.len(), 1);
+        assert_eq!(ended[0].0, "1");
+        assert_eq!(ended[0].1.lines, ["first", "at a", "at b"]);
+
+        let left = gatherer.finish();
+        assert_eq!(left.len(), 1);
+        assert_eq!(left[0].1.lines, ["other", "at c"]);
+        assert!(gatherer.finish().is_empty());
+    }
+
+    #[test]
+    fn leaves_out_lines_without_a_report() {
+        let gatherer = Gatherer::new();
+        gatherer.add("1", &log("1", "Trace", "at a"), false);
+        assert!(gatherer.finish().is_empty());
+    }
+}
@@ -0,0 +1,79 @@
+use crate::crash::JavaCrash;
+use crate::detector::gather::{Gatherer, Report};
+use crate::detector::Detector;
+use crate::event::Event;
+use crate::filter::PidFilter;
+use crate::log::Log;
+use crate::process::ProcessTable;
+use async_trait::async_trait;
+use crossbeam_channel::Sender;
+use std::sync::Arc;
+
+///
+/// Gathers the `AndroidRuntime` records of an uncaught exception into one crash.
+/// Long traces are split over several records and other processes log in between,
+/// so records are gathered per pid until the process logs something else.
+///
+pub struct JavaCrashDetector {
+    /// Traces being logged, by the pid logging them
+    traces: Gatherer,
+    events: Sender<Event>,
+    pid_filter: Arc<PidFilter>,
+    processes: Arc<ProcessTable>,
+}
+
+impl JavaCrashDetector {
+    #[allow(dead_code)]
+    pub fn new(
+        events: Sender<Event>,
+        pid_filter: Arc<PidFilter>,
+        processes: Arc<ProcessTable>,
+    ) -> Self {
+        Self {
+            traces: Gatherer::new(),
+            events,
+            pid_filter,
+            processes,
+        }
+    }
+
+    fn send(&self, pid: &str, trace: Report) {
+        let mut crash = JavaCrash::parse(pid, &trace.date, &trace.time, trace.lines);
+        if crash.name.is_empty() {
+            crash.name = self.processes.name(pid).unwrap_or_else(|| pid.to_string());
+        }
+        if self.pid_filter.follows(&crash.name) {
+            let _ = self.events.send(Event::JavaCrash(crash));
+        }
+    }
+
+    ///
+    /// Whether the log ends the trace being gathered for `pid`
+    ///
+    fn ends(log: &Log, pid: &str) -> bool {
+        if log.pid == pid {
+            return log.tag != "AndroidRuntime" || log.message.starts_with("FATAL EXCEPTION");
+        }
+        // am_crash: [user,pid,name,...] comes once the trace is logged
+        log.is_events() && log.tag == "am_crash" && log.message.contains(&format!(",{},", pid))
+    }
+}
+
+#[async_trait]
+impl Detector for JavaCrashDetector {
+    async fn detect(&self, log: &Log) {
+        for (pid, trace) in self.traces.ended(log, Self::ends) {
+            self.send(&pid, trace);
+        }
+        if log.tag == "AndroidRuntime" {
+            let starts = log.message.starts_with("FATAL EXCEPTION");
+            self.traces.add(&log.pid, log, starts);
+        }
+    }
+
+    async fn finish(&self) {
+        for (pid, trace) in self.traces.finish() {
+            self.send(&pid, trace);
+        }
+    }
+}
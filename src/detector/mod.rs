@@ -0,0 +1,28 @@
+mod anr;
+mod gather;
+mod java;
+mod native;
+
+#[allow(unused_imports)]
+pub(crate) use anr::AnrDetector;
+#[allow(unused_imports)]
+pub(crate) use java::JavaCrashDetector;
+#[allow(unused_imports)]
+pub(crate) use native::NativeCrashDetector;
+
+use crate::log::Log;
+
+use async_trait::async_trait;
+
+///
+/// Detector trait used to notice things spread over several records, like a crash.
+/// Detectors see every record before it is filtered and send what they find
+/// as events.
+///
+#[async_trait]
+pub trait Detector: Send + Sync {
+    async fn detect(&self, log: &Log);
+
+    /// The stream ended, send whatever is still being gathered
+    async fn finish(&self) {}
+}
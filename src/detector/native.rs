@@ -0,0 +1,130 @@
+use crate::crash::NativeCrash;
+use crate::detector::gather::{Gatherer, Report};
+use crate::detector::Detector;
+use crate::event::Event;
+use crate::filter::PidFilter;
+use crate::log::Log;
+use async_trait::async_trait;
+use crossbeam_channel::Sender;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// How long a 'Fatal signal' line waits for its tombstone
+const TOMBSTONE_WAIT: Duration = Duration::from_secs(5);
+
+struct Signal {
+    crash: NativeCrash,
+    seen: Instant,
+}
+
+///
+/// Gathers the tombstone `crash_dump` logs under the `DEBUG` tag into one crash.
+/// The tombstone is logged by crash_dump's own pid, the crashed process is read
+/// from its 'pid: 1234, tid: 1250, name: RenderThread  >>> com.foo <<<' line.
+///
+pub struct NativeCrashDetector {
+    /// Tombstones being logged, by the pid of the crash_dump logging them
+    tombstones: Gatherer,
+    /// 'Fatal signal' lines waiting for their tombstone, by the crashed pid
+    signals: Mutex<HashMap<String, Signal>>,
+    events: Sender<Event>,
+    pid_filter: Arc<PidFilter>,
+}
+
+impl NativeCrashDetector {
+    #[allow(dead_code)]
+    pub fn new(events: Sender<Event>, pid_filter: Arc<PidFilter>) -> Self {
+        Self {
+            tombstones: Gatherer::new(),
+            signals: Mutex::new(HashMap::new()),
+            events,
+            pid_filter,
+        }
+    }
+
+    fn send(&self, crash: NativeCrash) {
+        if self.pid_filter.follows(&crash.name) {
+            let _ = self.events.send(Event::NativeCrash(crash));
+        }
+    }
+
+    fn send_tombstone(&self, tombstone: Report) {
+        let mut crash = NativeCrash::parse(&tombstone.date, &tombstone.time, tombstone.lines);
+        if crash.pid.is_empty() {
+            return;
+        }
+        let signal = self.signals.lock().unwrap().remove(&crash.pid);
+        if let Some(signal) = signal {
+            if crash.signal.is_empty() {
+                crash.signal = signal.crash.signal;
+                crash.code = signal.crash.code;
+                crash.fault_addr = signal.crash.fault_addr;
+            }
+        }
+        self.send(crash);
+    }
+
+    ///
+    /// Whether the log ends the tombstone crash_dump `pid` is logging
+    ///
+    fn ends(log: &Log, pid: &str) -> bool {
+        log.pid == pid && (log.tag != "DEBUG" || log.message.contains("*** *** ***"))
+    }
+}
+
+#[async_trait]
+impl Detector for NativeCrashDetector {
+    async fn detect(&self, log: &Log) {
+        for (_, tombstone) in self.tombstones.ended(log, Self::ends) {
+            self.send_tombstone(tombstone);
+        }
+
+        // Without crash_dump there is only the signal to show
+        let expired = {
+            let mut signals = self.signals.lock().unwrap();
+            let pids = signals
+                .iter()
+                .filter(|(_, s)| s.seen.elapsed() > TOMBSTONE_WAIT)
+                .map(|(pid, _)| pid.clone())
+                .collect::<Vec<String>>();
+            pids.iter()
+                .map(|pid| signals.remove(pid).unwrap())
+                .collect::<Vec<Signal>>()
+        };
+        for signal in expired {
+            self.send(signal.crash);
+        }
+
+        match log.tag.as_str() {
+            "libc" => {
+                if let Some(crash) =
+                    NativeCrash::parse_fatal_signal(&log.date, &log.time, &log.message)
+                {
+                    self.signals.lock().unwrap().insert(
+                        crash.pid.clone(),
+                        Signal {
+                            crash,
+                            seen: Instant::now(),
+                        },
+                    );
+                }
+            }
+            "DEBUG" => {
+                let starts = log.message.contains("*** *** ***");
+                self.tombstones.add(&log.pid, log, starts);
+            }
+            _ => {}
+        }
+    }
+
+    async fn finish(&self) {
+        for (_, tombstone) in self.tombstones.finish() {
+            self.send_tombstone(tombstone);
+        }
+        let signals = std::mem::take(&mut *self.signals.lock().unwrap());
+        for (_, signal) in signals {
+            self.send(signal.crash);
+        }
+    }
+}
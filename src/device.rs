@@ -0,0 +1,128 @@
+use anyhow::{anyhow, Result};
+use chrono::{Duration, Local, NaiveDateTime};
+use lazy_static::lazy_static;
+use regex::Regex;
+use tokio::process::Command;
+
+lazy_static! {
+    static ref RESUMED_ACTIVITY: Regex = Regex::new(
+        r"(?:mResumedActivity|ResumedActivity|topResumedActivity)[:=]\s*ActivityRecord\{\S+ \S+ ([^/\s}]+)/"
+    )
+    .unwrap();
+    static ref FOCUSED_WINDOW: Regex =
+        Regex::new(r"(?:mCurrentFocus|mFocusedApp)=.*?\{\S+ \S+ ([^/\s}]+)/").unwrap();
+}
+
+///
+/// Runs adb shell commands against the selected device
+///
+#[derive(Clone)]
+pub(crate) struct Device {
+    serial: Option<String>,
+}
+
+impl Device {
+    pub fn new(serial: Option<String>) -> Self {
+        Self { serial }
+    }
+
+    async fn shell(&self, args: &[&str]) -> Result<String> {
+        let mut command = Command::new("adb");
+        if let Some(serial) = &self.serial {
+            command.arg("-s").arg(serial);
+        }
+        command.arg("shell");
+        command.args(args);
+        let output = command.output().await?;
+        if output.status.success() {
+            Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+        } else {
+            Err(anyhow!(
+                "adb shell {} failed: {}",
+                args.join(" "),
+                String::from_utf8_lossy(&output.stderr).trim()
+            ))
+        }
+    }
+
+    ///
+    /// Find the launcher activity of the package, as 'package/.Activity'
+    ///
+    async fn resolve_activity(&self, package: &str) -> Result<Option<String>> {
+        let out = self
+            .shell(&[
+                "cmd",
+                "package",
+                "resolve-activity",
+                "--brief",
+                "-c",
+                "android.intent.category.LAUNCHER",
+                package,
+            ])
+            .await?;
+        Ok(out
+            .lines()
+            .map(str::trim)
+            .rfind(|line| line.starts_with(package) && line.contains('/'))
+            .map(str::to_owned))
+    }
+
+    pub async fn force_stop(&self, package: &str) -> Result<()> {
+        self.shell(&["am", "force-stop", package]).await?;
+        Ok(())
+    }
+
+    ///
+    /// Start the launcher activity of the package
+    ///
+    pub async fn launch(&self, package: &str) -> Result<()> {
+        let out = match self.resolve_activity(package).await {
+            Ok(Some(component)) => self.shell(&["am", "start", "-n", &component]).await?,
+            // Older devices have no resolve-activity, let monkey find the launcher
+            _ => {
+                self.shell(&[
+                    "monkey",
+                    "-p",
+                    package,
+                    "-c",
+                    "android.intent.category.LAUNCHER",
+                    "1",
+                ])
+                .await?
+            }
+        };
+        match out.lines().find(|line| line.contains("Error")) {
+            Some(error) => Err(anyhow!("Failed to launch {}: {}", package, error.trim())),
+            None => Ok(()),
+        }
+    }
+
+    ///
+    /// Find the package of the foreground app
+    ///
+    pub async fn top_package(&self) -> Result<Option<String>> {
+        let out = self.shell(&["dumpsys", "activity", "activities"]).await?;
+        if let Some(cap) = RESUMED_ACTIVITY.captures(&out) {
+            return Ok(Some(cap[1].to_owned()));
+        }
+        let out = self.shell(&["dumpsys", "window"]).await?;
+        Ok(FOCUSED_WINDOW.captures(&out).map(|cap| cap[1].to_owned()))
+    }
+
+    ///
+    /// How far the host clock is ahead of the device clock, both in local time
+    ///
+    pub async fn clock_offset(&self) -> Result<Duration> {
+        let before = Local::now().naive_local();
+        let out = self.shell(&["date", "+%Y-%m-%d %H:%M:%S.%N"]).await?;
+        let after = Local::now().naive_local();
+        let host = before + (after - before) / 2;
+
+        // Older toolbox date has no %N, settle for whole seconds
+        let out = out.trim();
+        let device = NaiveDateTime::parse_from_str(out, "%Y-%m-%d %H:%M:%S%.f").or_else(|_| {
+            NaiveDateTime::parse_from_str(out.split('.').next().unwrap_or(out), "%Y-%m-%d %H:%M:%S")
+        })?;
+        Ok(host - device)
+    }
+}
@@ -0,0 +1,66 @@
+use crate::crash::{Anr, JavaCrash, NativeCrash};
+use std::fmt::{Display, Formatter};
+
+///
+/// Things noticed in the log stream that sinks may want to show
+/// on top of the records themselves.
+///
+#[derive(Debug, Clone)]
+pub enum Event {
+    /// A followed process was started, `reason` is like 'activity {com.foo/.Main}'
+    ProcessStart {
+        pid: String,
+        name: String,
+        reason: String,
+    },
+    /// A followed process died
+    ProcessDied { pid: String, name: String },
+    /// A followed process was killed by the system
+    ProcessKilled {
+        pid: String,
+        name: String,
+        reason: String,
+    },
+    /// A followed process crashed in native code
+    NativeCrash(NativeCrash),
+    /// A followed process died of an uncaught Java exception
+    JavaCrash(JavaCrash),
+    /// A followed process is not responding
+    Anr(Anr),
+    /// Another app came to the foreground under --follow-focus and is followed instead
+    FocusChanged { package: String },
+}
+
+impl Event {
+    ///
+    /// Lines to show under the banner, like the summary of a crash
+    ///
+    pub fn details(&self) -> Vec<String> {
+        match self {
+            Event::JavaCrash(crash) => crash.summary(),
+            Event::NativeCrash(crash) => crash.summary(),
+            Event::Anr(anr) => anr.summary(),
+            _ => Vec::new(),
+        }
+    }
+}
+
+impl Display for Event {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Event::ProcessStart { pid, name, reason } => {
+                write!(f, "Process {} (pid {}) started for {}", name, pid, reason)
+            }
+            Event::ProcessDied { pid, name } => write!(f, "Process {} (pid {}) died", name, pid),
+            Event::ProcessKilled { pid, name, reason } => {
+                write!(f, "Process {} (pid {}) killed: {}", name, pid, reason)
+            }
+            Event::NativeCrash(crash) => crash.fmt(f),
+            Event::JavaCrash(crash) => crash.fmt(f),
+            Event::Anr(anr) => anr.fmt(f),
+            Event::FocusChanged { package } => {
+                write!(f, "Following {}, now in the foreground", package)
+            }
+        }
+    }
+}
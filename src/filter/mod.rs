@@ -18,6 +18,7 @@ pub(crate) use tag_filter::TagFilter;
 use crate::log::Log;
 
 use async_trait::async_trait;
+use std::sync::Arc;
 
 ///
 /// Filter trait used to filter log
@@ -26,3 +27,10 @@ use async_trait::async_trait;
 pub trait Filter: Send + Sync {
     async fn filter(&self, log: &Log) -> bool;
 }
+
+#[async_trait]
+impl<T: Filter + ?Sized> Filter for Arc<T> {
+    async fn filter(&self, log: &Log) -> bool {
+        (**self).filter(log).await
+    }
+}
@@ -1,62 +1,67 @@
+use crate::event::Event;
 use crate::filter::Filter;
 use crate::log::Log;
+use crate::process::ProcessTable;
 use async_trait::async_trait;
+use crossbeam_channel::Sender;
 use dashmap::DashSet;
 use std::sync::atomic::{AtomicBool, Ordering};
-use tokio::io::AsyncBufReadExt;
-use tokio::process::{Child, Command};
+use std::sync::Arc;
 
 pub struct PidFilter {
     process: DashSet<String>,
     pids: DashSet<String>,
     first_filter: AtomicBool,
+    events: Sender<Event>,
+    processes: Arc<ProcessTable>,
 }
 
 impl PidFilter {
     #[allow(dead_code)]
-    pub fn new(process: Vec<String>) -> Self {
+    pub fn new(process: Vec<String>, events: Sender<Event>, processes: Arc<ProcessTable>) -> Self {
         Self {
-            process: DashSet::from_iter(process.into_iter()),
+            process: DashSet::from_iter(process),
             pids: DashSet::new(),
             first_filter: AtomicBool::new(true),
+            events,
+            processes,
         }
     }
 
-    async fn spawn_ps(&self) -> Child {
-        let mut command = Command::new("adb");
-        command.stdout(std::process::Stdio::piped());
-        command.arg("shell");
-        command.arg("ps");
-        command.spawn().expect("Failed to execute adb shell ps")
+    fn is_followed(&self, name: &str) -> bool {
+        self.process.iter().any(|p| name.contains(p.key().as_str()))
+    }
+
+    ///
+    /// Whether events about the process should be shown, every process when none is filtered
+    ///
+    #[allow(dead_code)]
+    pub fn follows(&self, name: &str) -> bool {
+        self.process.is_empty() || self.is_followed(name)
+    }
+
+    ///
+    /// Replace the filtered processes, the pids are looked up again on the next log
+    ///
+    #[allow(dead_code)]
+    pub fn set_process(&self, process: Vec<String>) {
+        self.process.clear();
+        for p in process {
+            self.process.insert(p);
+        }
+        self.pids.clear();
+        self.first_filter.store(true, Ordering::Release);
     }
 }
 
 #[async_trait]
 impl Filter for PidFilter {
     async fn filter(&self, log: &Log) -> bool {
-        if self.process.is_empty() {
-            return false;
-        }
-
         if self.first_filter.load(Ordering::Acquire) && self.pids.is_empty() {
-            let mut ps = self.spawn_ps().await;
-            let mut reader = tokio::io::BufReader::new(ps.stdout.take().unwrap());
-            let mut line = String::new();
-
-            while let Ok(bytes_read) = reader.read_line(&mut line).await {
-                if bytes_read == 0 {
-                    break;
+            for (pid, name) in self.processes.load().await {
+                if self.is_followed(&name) {
+                    self.pids.insert(pid);
                 }
-                let spl = line.trim().split_whitespace().collect::<Vec<&str>>();
-                let name = spl[8];
-                let pid = spl[1];
-                for p in self.process.iter() {
-                    let ptr = p.key().as_str();
-                    if name.contains(ptr) {
-                        self.pids.insert(pid.to_string());
-                    }
-                }
-                line.clear();
             }
             self.first_filter.store(false, Ordering::Release);
         }
@@ -67,29 +72,51 @@ impl Filter for PidFilter {
 
         match log.tag.as_ref() {
             "am_proc_start" => {
-                let spl = message[1..message.len() - 1]
-                    .split(",")
-                    .collect::<Vec<&str>>();
-                let pid = spl[1];
-                let name = spl[3];
-                for p in self.process.iter() {
-                    let ptr = p.key().as_str();
-                    if name.contains(ptr) {
+                let spl = fields(message, 6);
+                if let (Some(pid), Some(name)) = (spl.get(1), spl.get(3)) {
+                    self.processes.insert(pid, name);
+                    if self.is_followed(name) {
                         self.pids.insert(pid.to_string());
+                        let _ = self.events.send(Event::ProcessStart {
+                            pid: pid.to_string(),
+                            name: name.to_string(),
+                            reason: spl[4..].join(" "),
+                        });
                     }
                 }
             }
             "am_proc_died" => {
-                let spl = message[1..message.len() - 1]
-                    .split(",")
-                    .collect::<Vec<&str>>();
-                let pid = spl[1];
-                let _name = spl[2];
-                remove_pid = Some(pid.to_string());
+                let spl = fields(message, usize::MAX);
+                if let (Some(pid), Some(name)) = (spl.get(1), spl.get(2)) {
+                    if self.pids.contains(*pid) {
+                        let _ = self.events.send(Event::ProcessDied {
+                            pid: pid.to_string(),
+                            name: name.to_string(),
+                        });
+                    }
+                    remove_pid = Some(pid.to_string());
+                }
+            }
+            "am_kill" => {
+                let spl = fields(message, 5);
+                if let (Some(pid), Some(name)) = (spl.get(1), spl.get(2)) {
+                    if self.pids.contains(*pid) {
+                        let _ = self.events.send(Event::ProcessKilled {
+                            pid: pid.to_string(),
+                            name: name.to_string(),
+                            reason: spl.get(4).unwrap_or(&"").to_string(),
+                        });
+                    }
+                }
             }
             _ => {}
         }
 
+        // The process table is kept up to date even when not filtering
+        if self.process.is_empty() {
+            return false;
+        }
+
         let mut r = true;
 
         if self.pids.contains(&log.pid) {
@@ -107,6 +134,17 @@ impl Filter for PidFilter {
         if let Some(pid) = remove_pid {
             self.pids.remove(pid.as_str());
         }
-        return r;
+        r
     }
 }
+
+///
+/// The fields of an `[a,b,c]` event message, none when it is not one
+///
+fn fields(message: &str, n: usize) -> Vec<&str> {
+    message
+        .strip_prefix('[')
+        .and_then(|m| m.strip_suffix(']'))
+        .map(|m| m.splitn(n, ',').collect())
+        .unwrap_or_default()
+}
@@ -27,11 +27,7 @@ impl Filter for TagFilter {
         }
 
         if let Ok(re) = &self.re {
-            if re.is_match(&log.tag) || re.is_match(&log.message) {
-                false
-            } else {
-                true
-            }
+            !(re.is_match(&log.tag) || re.is_match(&log.message))
         } else {
             false
         }
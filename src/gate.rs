@@ -0,0 +1,132 @@
+use crate::event::Event;
+use crate::log::Log;
+use regex::Regex;
+
+///
+/// How a run ended, which decides the exit status
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Exit {
+    /// The stream ended, or whoever read it went away
+    Done,
+    /// The stream failed, like adb losing the device
+    Failed,
+    /// The stream ended before --until matched
+    Missed,
+    /// A record matched --until
+    Until,
+    /// A record or a crash matched --fail-on
+    FailOn,
+    /// --timeout passed before --until matched
+    Timeout,
+}
+
+impl Exit {
+    pub fn code(&self) -> i32 {
+        match self {
+            Exit::Done | Exit::Until => 0,
+            Exit::Failed => 1,
+            Exit::FailOn => 3,
+            Exit::Missed => 4,
+            Exit::Timeout => 124,
+        }
+    }
+}
+
+///
+/// What --fail-on waits for, a crash of a followed process or a message pattern
+///
+#[derive(Debug, Clone)]
+pub(crate) enum Condition {
+    Crash,
+    Pattern(Regex),
+}
+
+impl Condition {
+    pub fn parse(s: &str) -> Result<Self, String> {
+        if s == "crash" {
+            return Ok(Condition::Crash);
+        }
+        Regex::new(s)
+            .map(Condition::Pattern)
+            .map_err(|e| e.to_string())
+    }
+}
+
+///
+/// Ends the run on --until and --fail-on, for scripts waiting on the log
+///
+pub(crate) struct Gate {
+    until: Option<Regex>,
+    fail_on: Vec<Condition>,
+}
+
+impl Gate {
+    pub fn new(until: Option<Regex>, fail_on: Vec<Condition>) -> Self {
+        Self { until, fail_on }
+    }
+
+    ///
+    /// Whether a shown record ends the run, a failure wins over --until
+    ///
+    pub fn log(&self, log: &Log) -> Option<Exit> {
+        let fails = self.fail_on.iter().any(|c| match c {
+            Condition::Pattern(re) => re.is_match(&log.message),
+            Condition::Crash => false,
+        });
+        if fails {
+            return Some(Exit::FailOn);
+        }
+        match &self.until {
+            Some(re) if re.is_match(&log.message) => Some(Exit::Until),
+            _ => None,
+        }
+    }
+
+    pub fn event(&self, event: &Event) -> Option<Exit> {
+        let crash = matches!(event, Event::JavaCrash(_) | Event::NativeCrash(_));
+        if crash && self.fail_on.iter().any(|c| matches!(c, Condition::Crash)) {
+            Some(Exit::FailOn)
+        } else {
+            None
+        }
+    }
+
+    ///
+    /// How a run ends at --timeout, waiting for --until that never came is a failure
+    ///
+    pub fn timeout(&self) -> Exit {
+        if self.until.is_some() {
+            Exit::Timeout
+        } else {
+            Exit::Done
+        }
+    }
+
+    ///
+    /// How a run ends with the stream, ending before --until matched is a failure
+    ///
+    pub fn end(&self) -> Exit {
+        if self.until.is_some() {
+            Exit::Missed
+        } else {
+            Exit::Done
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fails_when_until_never_matched() {
+        let gate = Gate::new(None, Vec::new());
+        assert_eq!(gate.end(), Exit::Done);
+        assert_eq!(gate.timeout(), Exit::Done);
+
+        let gate = Gate::new(Some(Regex::new("ready").unwrap()), Vec::new());
+        assert_eq!(gate.end().code(), 4);
+        assert_eq!(gate.timeout().code(), 124);
+    }
+}
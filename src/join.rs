@@ -0,0 +1,230 @@
+use crate::log::{Log, LogStream};
+use async_stream::stream;
+use futures::StreamExt;
+use std::time::{Duration, Instant};
+
+/// How long a record waits for its continuation on a live stream
+const FLUSH: Duration = Duration::from_millis(100);
+/// A part this long was cut at the logd limit rather than at a line break
+const CUT: usize = 4000;
+
+struct Pending {
+    log: Log,
+    /// The log time of its last part
+    last: Option<u64>,
+    /// The size of its last part
+    size: usize,
+    /// When it stops waiting for another part on a live stream
+    deadline: Instant,
+}
+
+///
+/// Join records of the same pid, tid, tag and level logged within `window` of each
+/// other into one, like a JSON payload or a stack trace split over several. Other
+/// threads log in between, so each of them has a record of its own waiting.
+/// A part cut at the size limit runs straight into the next one, the others are
+/// joined as lines.
+///
+pub(crate) fn join(mut logs: LogStream, window: Duration) -> LogStream {
+    let wait = FLUSH.max(window);
+    let s = stream! {
+        // In the order they started, records go out in that order too
+        let mut pending: Vec<Pending> = Vec::new();
+        loop {
+            let next = match pending.iter().map(|p| p.deadline).min() {
+                Some(deadline) => {
+                    match tokio::time::timeout_at(deadline.into(), logs.next()).await {
+                        Ok(next) => next,
+                        Err(_) => {
+                            let now = Instant::now();
+                            let done = pending
+                                .iter()
+                                .rposition(|p| p.deadline <= now)
+                                .map_or(0, |i| i + 1);
+                            for p in pending.drain(..done) {
+                                yield Ok(p.log);
+                            }
+                            continue;
+                        }
+                    }
+                }
+                None => logs.next().await,
+            };
+            let log = match next {
+                Some(Ok(log)) => log,
+                Some(Err(e)) => {
+                    yield Err(e);
+                    continue;
+                }
+                None => break,
+            };
+
+            // Each event and crash record stands on its own, the filters and detectors
+            // parse them, after whatever was logged before them
+            if log.is_events() || log.is_crash() {
+                for p in pending.drain(..) {
+                    yield Ok(p.log);
+                }
+                yield Ok(log);
+                continue;
+            }
+
+            let time = millis(&log.time);
+            // Records too old to be continued go out, with those started before them
+            let done = pending
+                .iter()
+                .rposition(|p| !within(p.last, time, window))
+                .map_or(0, |i| i + 1);
+            for p in pending.drain(..done) {
+                yield Ok(p.log);
+            }
+
+            match pending.iter_mut().find(|p| same(&p.log, &log)) {
+                Some(p) => {
+                    if p.size < CUT {
+                        p.log.message.push('\n');
+                    }
+                    p.log.message.push_str(&log.message);
+                    p.last = time;
+                    p.size = log.message.len();
+                    p.deadline = Instant::now() + wait;
+                }
+                None => pending.push(Pending {
+                    size: log.message.len(),
+                    last: time,
+                    deadline: Instant::now() + wait,
+                    log,
+                }),
+            }
+        }
+        for p in pending {
+            yield Ok(p.log);
+        }
+    };
+    Box::pin(s)
+}
+
+fn same(a: &Log, b: &Log) -> bool {
+    a.pid == b.pid && a.tid == b.tid && a.tag == b.tag && a.level == b.level && a.buffer == b.buffer
+}
+
+///
+/// Whether a record logged at `time` is within `window` of a part logged at `last`
+///
+fn within(last: Option<u64>, time: Option<u64>, window: Duration) -> bool {
+    match (last, time) {
+        (Some(last), Some(time)) => time >= last && time - last <= window.as_millis() as u64,
+        _ => false,
+    }
+}
+
+///
+/// Milliseconds into the day of a 'hh:mm:ss.mmm' log time
+///
+fn millis(time: &str) -> Option<u64> {
+    let (hms, ms) = time.split_once('.')?;
+    let mut parts = hms.split(':').map(|p| p.parse::<u64>().ok());
+    let (h, m, s) = (parts.next()??, parts.next()??, parts.next()??);
+    Some(((h * 60 + m) * 60 + s) * 1000 + ms.parse::<u64>().ok()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn log(time: &str, level: &str, buffer: &str, message: &str) -> Log {
+        Log {
+            date: "10-19".to_string(),
+            time: time.to_string(),
+            pid: "1234".to_string(),
+            tid: "1234".to_string(),
+            level: level.to_string(),
+            tag: "Http".to_string(),
+            message: message.to_string(),
+            buffer: buffer.to_string(),
+        }
+    }
+
+    async fn joined(logs: Vec<Log>) -> Vec<String> {
+        let logs: LogStream = Box::pin(futures::stream::iter(logs.into_iter().map(Ok)));
+        join(logs, Duration::from_millis(20))
+            .map(|r| r.unwrap().message)
+            .collect()
+            .await
+    }
+
+    #[test]
+    fn parses_log_times() {
+        assert_eq!(millis("00:00:01.002"), Some(1002));
+        assert_eq!(millis("12:34:56.789"), Some(45_296_789));
+        assert_eq!(millis("12:34:56"), None);
+        assert_eq!(millis("xx:34:56.789"), None);
+    }
+
+    #[tokio::test]
+    async fn runs_cut_parts_together() {
+        let cut = "a".repeat(CUT);
+        let messages = joined(vec![
+            log("12:00:00.000", "D", "main", &cut),
+            log("12:00:00.001", "D", "main", "b\"}"),
+        ])
+        .await;
+        assert_eq!(messages, [format!("{}b\"}}", cut)]);
+    }
+
+    #[tokio::test]
+    async fn joins_short_parts_as_lines() {
+        let messages = joined(vec![
+            log("12:00:00.000", "D", "main", "first"),
+            log("12:00:00.010", "D", "main", "second"),
+            log("12:00:00.100", "D", "main", "later"),
+        ])
+        .await;
+        assert_eq!(messages, ["first\nsecond", "later"]);
+    }
+
+    #[tokio::test]
+    async fn keeps_levels_and_events_apart() {
+        let messages = joined(vec![
+            log("12:00:00.000", "D", "main", "debug"),
+            log("12:00:00.001", "E", "main", "error"),
+            log("12:00:00.002", "I", "events", "[0,1234]"),
+            log("12:00:00.003", "I", "events", "[0,1235]"),
+            log("12:00:00.004", "F", "crash", "FATAL"),
+            log("12:00:00.005", "F", "crash", "at a"),
+        ])
+        .await;
+        assert_eq!(
+            messages,
+            ["debug", "error", "[0,1234]", "[0,1235]", "FATAL", "at a"]
+        );
+    }
+
+    #[tokio::test]
+    async fn flushes_on_a_live_stream() {
+        let logs: LogStream = Box::pin(
+            futures::stream::iter([Ok(log("12:00:00.000", "D", "main", "alone"))])
+                .chain(futures::stream::pending()),
+        );
+        let mut joined = join(logs, Duration::from_millis(20));
+        let first = tokio::time::timeout(Duration::from_secs(1), joined.next()).await;
+        assert_eq!(first.unwrap().unwrap().unwrap().message, "alone");
+    }
+
+    #[tokio::test]
+    async fn joins_interleaved_threads_apart() {
+        let mut other = log("12:00:00.001", "D", "main", "other");
+        other.tid = "1250".to_string();
+        let mut other_more = log("12:00:00.003", "D", "main", "other more");
+        other_more.tid = "1250".to_string();
+        let messages = joined(vec![
+            log("12:00:00.000", "D", "main", "first"),
+            other,
+            log("12:00:00.002", "D", "main", "second"),
+            other_more,
+            log("12:00:00.100", "D", "main", "later"),
+        ])
+        .await;
+        assert_eq!(messages, ["first\nsecond", "other\nother more", "later"]);
+    }
+}
@@ -1,9 +1,25 @@
 mod log;
 pub use log::{Log, LogStream};
 
+mod crash;
+pub use crash::{Anr, JavaCrash, JavaException, NativeCrash, NativeFrame};
+
+mod event;
+pub use event::Event;
+
+mod retrace;
+pub use retrace::Mapping;
+
+mod symbols;
+pub use symbols::Symbols;
+
 pub mod source;
 
+mod detector;
+pub use detector::Detector;
+
 mod filter;
+mod process;
 pub use filter::Filter;
 
 mod sink;
@@ -37,6 +37,8 @@ pub struct Log {
 /// ```no_run
 /// use futures::StreamExt;
 /// use pidcat::source::{ADBSource, Source};
+/// # #[tokio::main]
+/// # async fn main() {
 /// let source = ADBSource::new(None);
 /// let mut logs = source.source().await;
 /// while let Some(r) = logs.next().await {
@@ -47,6 +49,7 @@ pub struct Log {
 ///         Err(_) => {}
 ///     }
 /// }
+/// # }
 /// ```
 ///
 pub type LogStream = Pin<Box<dyn Stream<Item = Result<Log, Box<dyn Error + Send>>> + Send>>;
@@ -1,70 +1,337 @@
 use crate::cli::Cli;
+use crate::context::{Context, Line};
+use crate::detector::{AnrDetector, Detector, JavaCrashDetector, NativeCrashDetector};
+use crate::device::Device;
+use crate::event::Event;
 use crate::filter::{BufferFilter, Filter, LevelFilter, PidFilter, RevertFilter, TagFilter};
-use crate::sink::{FileSink, Sink, TerminalSink};
+use crate::gate::{Exit, Gate};
+use crate::join::join;
+use crate::pager::Pager;
+use crate::process::ProcessTable;
+use crate::sink::{
+    CrashSink, FileSink, Pretty, Sink, Template, TerminalSink, TimeMode, Timestamps,
+};
 use crate::source::{ADBSource, Source};
-use anyhow::Result;
+use crate::suppress::{Suppressor, QUIET};
+use crate::symbols::Symbols;
+use crate::tui::{Passthrough, Tui};
+use anyhow::{anyhow, Result};
+use crossbeam_channel::Sender;
 use futures::StreamExt;
-use tokio::process::{Child, Command};
+use std::io::{IsTerminal, Write};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::task::JoinHandle;
 
 mod cli;
+mod context;
+mod crash;
+mod detector;
+mod device;
+mod event;
 mod filter;
+mod gate;
+mod join;
 mod log;
+mod pager;
+mod process;
+mod retrace;
 mod sink;
 mod source;
+mod suppress;
+mod symbols;
+mod tui;
 
-async fn run() -> Result<()> {
+async fn run() -> Result<Exit> {
     let cli = cli::cli()?;
-    let _ = which::which("adb")?;
-
-    tokio::spawn(async move {
-        if cli.clear {
-            spawn_adb_logcat_clear().await;
-        } else {
-            fetch(cli).await;
-        }
-    })
-    .await?;
+    if let Err(e) = which::which("adb") {
+        eprintln!("adb: {}", e);
+        return Err(e.into());
+    }
 
-    Ok(())
+    tokio::spawn(async move { fetch(cli).await }).await?
 }
 
 #[tokio::main]
 async fn main() {
-    match run().await {
-        Ok(_) => {}
-        Err(_) => {}
-    }
+    // Errors are reported where they happen
+    let code = match run().await {
+        Ok(exit) => exit.code(),
+        Err(_) => 1,
+    };
+    std::process::exit(code);
 }
 
-async fn fetch(cli: Cli) {
-    let source = ADBSource::new(if cli.device.is_empty() {
+async fn fetch(mut cli: Cli) -> Result<Exit> {
+    let serial = if cli.device.is_empty() {
         None
     } else {
-        Some(cli.device)
-    });
+        Some(cli.device.clone())
+    };
+    if cli.tui && !std::io::stdout().is_terminal() {
+        let e = anyhow!("--tui needs a terminal, stdout is redirected");
+        eprintln!("{}", e);
+        return Err(e);
+    }
+    let device = Device::new(serial.clone());
+    let processes = Arc::new(ProcessTable::new(serial.clone()));
+    let mut source = ADBSource::with_mode(serial, cli.dump, cli.since.clone());
+    // Ctrl-C goes to the pager, like to leave less's follow mode, not to logcat
+    if cli.pager && std::io::stdout().is_terminal() {
+        source = source.detached();
+    }
+
+    if let Some(package) = &cli.launch {
+        if cli.force_stop {
+            if let Err(e) = device.force_stop(package).await {
+                eprintln!("{}", e);
+                return Err(e);
+            }
+        }
+        if !cli.process.contains(package) {
+            cli.process.push(package.clone());
+        }
+    }
+
+    if cli.current || cli.follow_focus {
+        match device.top_package().await {
+            Ok(Some(package)) => cli.process = vec![package],
+            Ok(None) => {
+                eprintln!("No app in the foreground");
+                return Err(anyhow!("No app in the foreground"));
+            }
+            Err(e) => {
+                eprintln!("{}", e);
+                return Err(e);
+            }
+        }
+    }
+
+    if cli.clear {
+        if let Err(e) = source.clear(&cli.buffers).await {
+            eprintln!("{}", e);
+            return Err(e);
+        }
+        eprintln!("Cleared {} log buffers", cli.buffers.join(", "));
+        if !cli.follow && cli.launch.is_none() {
+            return Ok(Exit::Done);
+        }
+    }
+
+    let (events, events_rx) = crossbeam_channel::unbounded();
+    let pid_filter = Arc::new(PidFilter::new(
+        cli.process.clone(),
+        events.clone(),
+        processes.clone(),
+    ));
+    if cli.follow_focus {
+        tokio::spawn(follow_focus(
+            device.clone(),
+            cli.process.clone(),
+            pid_filter.clone(),
+            events.clone(),
+        ));
+    }
 
-    let filters: Vec<Box<dyn Filter>> = vec![
-        Box::new(PidFilter::new(cli.process)),
-        Box::new(BufferFilter::new(cli.buffers)),
-        Box::new(LevelFilter::new(cli.level)),
-        Box::new(TagFilter::new(cli.tag, cli.ignore)),
-        Box::new(RevertFilter::new(cli.revert, cli.ignore)),
+    // Detectors see every record, a crash is worth showing even when its lines are filtered out
+    let detectors: Vec<Box<dyn Detector>> = vec![
+        Box::new(JavaCrashDetector::new(
+            events.clone(),
+            pid_filter.clone(),
+            processes.clone(),
+        )),
+        Box::new(NativeCrashDetector::new(events.clone(), pid_filter.clone())),
+        Box::new(AnrDetector::new(events, pid_filter.clone())),
     ];
 
+    // The viewer filters on its own so it can change its mind about the history
+    let filters: Vec<Box<dyn Filter>> = if cli.tui {
+        vec![
+            Box::new(Passthrough(pid_filter.clone())),
+            Box::new(BufferFilter::new(cli.buffers.clone())),
+        ]
+    } else {
+        vec![
+            Box::new(pid_filter.clone()),
+            Box::new(BufferFilter::new(cli.buffers.clone())),
+        ]
+    };
+    // What these drop can still be shown as context around what they let through
+    let matchers: Vec<Box<dyn Filter>> = if cli.tui {
+        Vec::new()
+    } else {
+        vec![
+            Box::new(LevelFilter::new(cli.level)),
+            Box::new(TagFilter::new(cli.tag.clone(), cli.ignore)),
+            Box::new(RevertFilter::new(cli.revert.clone(), cli.ignore)),
+        ]
+    };
+    let mut context = Context::new(cli.before, cli.after);
+    let mut suppressor = Suppressor::new(cli.dedup, cli.rate_limit);
+
     let mut sinks: Vec<Box<dyn Sink>> = Vec::new();
 
-    sinks.push(Box::new(TerminalSink::new(cli.color, cli.tag_width)));
+    let terminal_template = cli
+        .format
+        .clone()
+        .unwrap_or_else(|| Template::terminal(cli.tag_width, cli.process_names, cli.thread_names))
+        .define("device", &cli.device)
+        .with_processes(processes.clone());
+    let file_template = cli
+        .format
+        .clone()
+        .unwrap_or_else(Template::file)
+        .define("device", &cli.device)
+        .with_processes(processes.clone());
+
+    let time = match cli.time.as_str() {
+        "relative" => TimeMode::Relative,
+        "delta" => TimeMode::Delta,
+        "marker" => TimeMode::Marker(cli.marker.clone().unwrap()),
+        "local" => match device.clock_offset().await {
+            Ok(offset) => TimeMode::Local(offset),
+            Err(e) => {
+                eprintln!("{}", e);
+                return Err(e);
+            }
+        },
+        _ => TimeMode::Device,
+    };
+
+    let mut tui = None;
+    let mut pager = None;
+    if cli.tui {
+        let (sink, viewer) = Tui::new(&cli, terminal_template, pid_filter, processes);
+        sinks.push(Box::new(sink));
+        tui = Some(viewer);
+    } else {
+        // Paging only makes sense on a terminal, a pipe gets the output as is
+        let out: Box<dyn Write + Send> = if cli.pager && std::io::stdout().is_terminal() {
+            match Pager::spawn() {
+                Ok((p, stdin)) => {
+                    pager = Some(p);
+                    Box::new(stdin)
+                }
+                Err(e) => {
+                    eprintln!("{}", e);
+                    return Err(e);
+                }
+            }
+        } else {
+            Box::new(std::io::stdout())
+        };
+        let mut terminal = TerminalSink::new(
+            out,
+            cli.color,
+            terminal_template,
+            cli.theme,
+            cli.color_by,
+            Timestamps::new(time),
+            cli.highlights,
+        );
+        if let Some(kind) = &cli.pretty {
+            terminal = terminal.with_pretty(Pretty::new(kind));
+        }
+        sinks.push(Box::new(terminal));
+    }
     if let Some(file) = cli.output {
-        if let Ok(file) = FileSink::new(file).await {
+        if let Ok(file) = FileSink::new(file, file_template).await {
             sinks.push(Box::new(file));
         }
     }
+    if let Some(dir) = cli.crash_dir {
+        match CrashSink::new(dir, cli.device.clone(), cli.pull_anr).await {
+            Ok(crashes) => sinks.push(Box::new(crashes)),
+            Err(e) => {
+                eprintln!("{}", e);
+                return Err(e);
+            }
+        }
+    }
 
+    // logcat is already attached when the app starts, so PidFilter picks up
+    // the new pid from am_proc_start before its first lines arrive
     let mut logs = source.source().await;
+    if let Some(window) = cli.join {
+        logs = join(logs, window);
+    }
+
+    if let Some(package) = &cli.launch {
+        if let Err(e) = device.launch(package).await {
+            eprintln!("{}", e);
+            return Err(e);
+        }
+    }
+
+    let symbols = match &cli.symbols {
+        Some(dir) => match Symbols::new(dir) {
+            Ok(symbols) => Some(symbols),
+            Err(e) => {
+                eprintln!("{}", e);
+                return Err(e);
+            }
+        },
+        None => None,
+    };
+    let mapping = cli.mapping.clone();
+    let gate = Gate::new(cli.until.clone(), cli.fail_on.clone());
+    let timeout = gate.timeout();
+    // Never passes without --timeout
+    let deadline = async move {
+        match cli.timeout {
+            Some(timeout) => tokio::time::sleep(timeout).await,
+            None => std::future::pending().await,
+        }
+    };
+    tokio::pin!(deadline);
+    let mut pump = tokio::spawn(async move {
+        let exit = async {
+            let mut failed = false;
+            loop {
+                // A flood that stops still gets its notes on a live stream
+                let next = if suppressor.pending() {
+                    match tokio::time::timeout(QUIET, logs.next()).await {
+                        Ok(next) => next,
+                        Err(_) => {
+                            for note in suppressor.finish() {
+                                for sink in &sinks {
+                                    sink.context(note.clone()).await;
+                                }
+                            }
+                            continue;
+                        }
+                    }
+                } else {
+                    logs.next().await
+                };
+                let Some(r) = next else {
+                    break;
+                };
+                let mut log = match r {
+                    Ok(log) => log,
+                    Err(e) => {
+                        // Like adb losing the device, the stream ends right after
+                        eprintln!("{}", e);
+                        failed = true;
+                        continue;
+                    }
+                };
+                // Before the detectors too, so crash reports read deobfuscated
+                if let Some(mapping) = &mapping {
+                    if let Some(message) = mapping.retrace(&log.message) {
+                        log.message = message;
+                    }
+                }
+                if let Some(symbols) = &symbols {
+                    if let Some(message) = symbols.symbolize(&log.message) {
+                        log.message = message;
+                    }
+                }
+
+                for detector in &detectors {
+                    detector.detect(&log).await;
+                }
 
-    while let Some(r) = logs.next().await {
-        match r {
-            Ok(log) => {
                 let mut is_filter = false;
                 for filter in &filters {
                     if filter.filter(&log).await {
@@ -72,22 +339,161 @@ async fn fetch(cli: Cli) {
                         break;
                     }
                 }
-
+                let mut is_hidden = false;
                 if !is_filter {
+                    for matcher in &matchers {
+                        if matcher.filter(&log).await {
+                            is_hidden = true;
+                            break;
+                        }
+                    }
+                }
+
+                for event in events_rx.try_iter() {
                     for sink in &sinks {
-                        sink.write(log.clone()).await;
+                        sink.event(&event).await;
+                    }
+                    if let Some(exit) = gate.event(&event) {
+                        return exit;
                     }
                 }
+
+                if is_filter {
+                    // Another process, or a buffer that wasn't asked for
+                } else if is_hidden {
+                    if let Some(log) = context.hidden(&log) {
+                        for sink in &sinks {
+                            sink.context(log.clone()).await;
+                        }
+                    }
+                } else {
+                    let (notes, show) = suppressor.check(&log);
+                    for note in notes {
+                        for sink in &sinks {
+                            sink.context(note.clone()).await;
+                        }
+                    }
+                    if show {
+                        for line in context.matched(&log) {
+                            for sink in &sinks {
+                                match &line {
+                                    Line::Context(log) => sink.context(log.clone()).await,
+                                    Line::Separator => sink.separator().await,
+                                }
+                            }
+                        }
+                        for sink in &sinks {
+                            sink.write(log.clone()).await;
+                        }
+                    }
+                    // Even when suppressed, a script waiting for it should not miss it
+                    if let Some(exit) = gate.log(&log) {
+                        return exit;
+                    }
+                }
+
+                if sinks.iter().any(|sink| sink.closed()) {
+                    return Exit::Done;
+                }
             }
-            Err(_) => {}
+
+            for note in suppressor.finish() {
+                for sink in &sinks {
+                    sink.context(note.clone()).await;
+                }
+            }
+            for detector in &detectors {
+                detector.finish().await;
+            }
+            for event in events_rx.try_iter() {
+                for sink in &sinks {
+                    sink.event(&event).await;
+                }
+                if let Some(exit) = gate.event(&event) {
+                    return exit;
+                }
+            }
+            if failed {
+                Exit::Failed
+            } else {
+                gate.end()
+            }
+        }
+        .await;
+        // Like crash reports still pulling traces
+        for sink in &sinks {
+            sink.finish().await;
+        }
+        exit
+    });
+
+    match (tui, pager) {
+        // The viewer stays up after a dump ends, until it is closed
+        (Some(tui), _) => {
+            let r = tui.run().await;
+            abort(&mut pump).await;
+            if let Err(e) = &r {
+                eprintln!("{}", e);
+            }
+            r.map(|_| Exit::Done)
+        }
+        (None, Some(pager)) => {
+            // Ctrl-C belongs to the pager, we go when it goes
+            tokio::spawn(async { while tokio::signal::ctrl_c().await.is_ok() {} });
+            let quit = pager.wait();
+            tokio::pin!(quit);
+            let exit = tokio::select! {
+                // The pipe closes with the stream, leaving the pager up to be read
+                r = &mut pump => {
+                    let exit = r?;
+                    quit.await;
+                    exit
+                }
+                _ = &mut deadline => {
+                    abort(&mut pump).await;
+                    quit.await;
+                    timeout
+                }
+                _ = &mut quit => {
+                    abort(&mut pump).await;
+                    Exit::Done
+                }
+            };
+            Ok(exit)
         }
+        (None, None) => tokio::select! {
+            r = &mut pump => Ok(r?),
+            _ = &mut deadline => {
+                abort(&mut pump).await;
+                Ok(timeout)
+            }
+        },
     }
 }
 
-async fn spawn_adb_logcat_clear() -> Child {
-    let mut command = Command::new("adb");
-    command.stdout(std::process::Stdio::piped());
-    command.arg("logcat");
-    command.arg("-c");
-    command.spawn().expect("Failed to execute adb logcat -c")
+///
+/// Stop the pump and wait for it to drop the stream, killing adb logcat before we exit
+///
+async fn abort(pump: &mut JoinHandle<Exit>) {
+    pump.abort();
+    let _ = pump.await;
+}
+
+async fn follow_focus(
+    device: Device,
+    mut process: Vec<String>,
+    pid_filter: Arc<PidFilter>,
+    events: Sender<Event>,
+) {
+    loop {
+        tokio::time::sleep(Duration::from_secs(1)).await;
+        if let Ok(Some(package)) = device.top_package().await {
+            if !process.contains(&package) {
+                process = vec![package.clone()];
+                pid_filter.set_process(process.clone());
+                // The viewer filters on its own and switches on the event
+                let _ = events.send(Event::FocusChanged { package });
+            }
+        }
+    }
 }
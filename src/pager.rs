@@ -0,0 +1,46 @@
+use anyhow::{anyhow, Result};
+use std::env;
+use std::process::{Child, ChildStdin, Command, Stdio};
+
+///
+/// A pager reading our output, `$PAGER` or `less -R`
+///
+pub(crate) struct Pager {
+    child: Child,
+}
+
+impl Pager {
+    ///
+    /// Start the pager on the terminal, returns it with the pipe to write into
+    ///
+    pub fn spawn() -> Result<(Self, ChildStdin)> {
+        let command = env::var("PAGER")
+            .ok()
+            .filter(|p| !p.trim().is_empty())
+            .unwrap_or_else(|| "less -R".to_string());
+        // Split like the shell would, `less --prompt="a b"` has one argument
+        let args =
+            shell_words::split(&command).map_err(|e| anyhow!("Bad pager '{}': {}", command, e))?;
+        let Some((program, args)) = args.split_first() else {
+            return Err(anyhow!("Bad pager '{}'", command));
+        };
+        let mut pager = Command::new(program);
+        pager.args(args).stdin(Stdio::piped());
+        // Like git, keep the colors when $PAGER is a plain less
+        if env::var_os("LESS").is_none() {
+            pager.env("LESS", "R");
+        }
+        let mut child = pager
+            .spawn()
+            .map_err(|e| anyhow!("Failed to start pager '{}': {}", command, e))?;
+        let stdin = child.stdin.take().unwrap();
+        Ok((Self { child }, stdin))
+    }
+
+    ///
+    /// Wait for the user to quit the pager
+    ///
+    pub async fn wait(mut self) {
+        let _ = tokio::task::spawn_blocking(move || self.child.wait()).await;
+    }
+}
@@ -0,0 +1,177 @@
+use dashmap::mapref::entry::Entry;
+use dashmap::{DashMap, DashSet};
+use std::sync::Arc;
+use tokio::process::Command;
+use tokio::sync::Semaphore;
+
+/// Processes whose threads are looked up at the same time
+const LOOKUPS: usize = 4;
+
+///
+/// Names of the device's processes and threads, keyed by pid and tid
+///
+#[derive(Debug)]
+pub(crate) struct ProcessTable {
+    serial: Option<String>,
+    processes: DashMap<String, String>,
+    /// By pid and tid, a tid can be reused by another process
+    threads: DashMap<(String, String), String>,
+    /// Processes whose threads were listed with `ps -T`
+    scanned: DashSet<String>,
+    /// Threads being looked up in the background
+    pending: DashSet<(String, String)>,
+    /// Tids waiting by pid, one task per pid works through them after its `ps -T`
+    queued: DashMap<String, Vec<String>>,
+    lookups: Semaphore,
+}
+
+#[allow(dead_code)]
+impl ProcessTable {
+    pub fn new(serial: Option<String>) -> Self {
+        Self {
+            serial,
+            processes: DashMap::new(),
+            threads: DashMap::new(),
+            scanned: DashSet::new(),
+            pending: DashSet::new(),
+            queued: DashMap::new(),
+            lookups: Semaphore::new(LOOKUPS),
+        }
+    }
+
+    async fn shell(&self, args: &[&str]) -> Option<String> {
+        let mut command = Command::new("adb");
+        if let Some(serial) = &self.serial {
+            command.arg("-s").arg(serial);
+        }
+        command.arg("shell");
+        command.args(args);
+        let output = command.output().await.ok()?;
+        if output.status.success() {
+            Some(String::from_utf8_lossy(&output.stdout).into_owned())
+        } else {
+            None
+        }
+    }
+
+    ///
+    /// Read the running processes with `ps`, returns the (pid, name) pairs found
+    ///
+    pub async fn load(&self) -> Vec<(String, String)> {
+        // Since Android 8 plain ps only lists the shell's own processes
+        let mut out = self.shell(&["ps", "-A"]).await.unwrap_or_default();
+        if out.lines().count() <= 1 {
+            out = self.shell(&["ps"]).await.unwrap_or_default();
+        }
+        let mut found = Vec::new();
+        for line in out.lines().skip(1) {
+            let spl = line.split_whitespace().collect::<Vec<&str>>();
+            if spl.len() < 9 {
+                continue;
+            }
+            let (pid, name) = (spl[1], spl[8]);
+            self.processes.insert(pid.to_string(), name.to_string());
+            found.push((pid.to_string(), name.to_string()));
+        }
+        found
+    }
+
+    pub fn insert(&self, pid: &str, name: &str) {
+        self.processes.insert(pid.to_string(), name.to_string());
+        // A reused pid is a new process with new threads
+        self.scanned.remove(pid);
+        self.threads.retain(|(p, _), _| p != pid);
+    }
+
+    pub fn name(&self, pid: &str) -> Option<String> {
+        self.processes.get(pid).map(|name| name.value().clone())
+    }
+
+    pub fn cached_thread_name(&self, pid: &str, tid: &str) -> Option<String> {
+        self.threads
+            .get(&(pid.to_string(), tid.to_string()))
+            .map(|name| name.value().clone())
+    }
+
+    ///
+    /// Look up a thread name in the background when it is not known yet, the lines
+    /// written meanwhile show the tid
+    ///
+    pub fn request_thread_name(self: &Arc<Self>, pid: &str, tid: &str) {
+        let key = (pid.to_string(), tid.to_string());
+        if self.threads.contains_key(&key) || !self.pending.insert(key) {
+            return;
+        }
+        match self.queued.entry(pid.to_string()) {
+            Entry::Occupied(mut e) => e.get_mut().push(tid.to_string()),
+            Entry::Vacant(e) => {
+                e.insert(vec![tid.to_string()]);
+                let table = self.clone();
+                let pid = pid.to_string();
+                tokio::spawn(async move { table.work_through(pid).await });
+            }
+        }
+    }
+
+    ///
+    /// Look up the tids queued for a process until none are left, the first lookup
+    /// lists its threads so most of the others are found in the cache
+    ///
+    async fn work_through(&self, pid: String) {
+        let Ok(_permit) = self.lookups.acquire().await else {
+            return;
+        };
+        loop {
+            let tids = match self.queued.entry(pid.clone()) {
+                Entry::Occupied(mut e) if !e.get().is_empty() => std::mem::take(e.get_mut()),
+                Entry::Occupied(e) => {
+                    e.remove();
+                    return;
+                }
+                Entry::Vacant(_) => return,
+            };
+            for tid in tids {
+                self.thread_name(&pid, &tid).await;
+                self.pending.remove(&(pid.clone(), tid));
+            }
+        }
+    }
+
+    ///
+    /// Look up a thread name, listing the process's threads with `ps -T` the first
+    /// time and reading `/proc/<pid>/task/<tid>/comm` for threads started later
+    ///
+    pub async fn thread_name(&self, pid: &str, tid: &str) -> Option<String> {
+        if let Some(name) = self.cached_thread_name(pid, tid) {
+            return Some(name);
+        }
+        if self.scanned.insert(pid.to_string()) {
+            let out = self
+                .shell(&["ps", "-T", "-p", pid])
+                .await
+                .unwrap_or_default();
+            for line in out.lines().skip(1) {
+                let spl = line.split_whitespace().collect::<Vec<&str>>();
+                if spl.len() >= 10 && spl[1] == pid {
+                    self.threads
+                        .insert((pid.to_string(), spl[2].to_string()), spl[9..].join(" "));
+                }
+            }
+            if let Some(name) = self.cached_thread_name(pid, tid) {
+                return Some(name);
+            }
+        }
+        let path = format!("/proc/{}/task/{}/comm", pid, tid);
+        let name = self
+            .shell(&["cat", &path])
+            .await
+            .map(|s| s.trim().to_string())
+            .filter(|s| !s.is_empty() && !s.contains("No such file"));
+        // Remember misses too, a thread that is gone stays gone
+        self.threads.insert(
+            (pid.to_string(), tid.to_string()),
+            name.clone().unwrap_or_default(),
+        );
+        name
+    }
+}
@@ -0,0 +1,338 @@
+use anyhow::{anyhow, Result};
+use lazy_static::lazy_static;
+use regex::Regex;
+use std::collections::HashMap;
+use std::path::Path;
+
+lazy_static! {
+    static ref CLASS: Regex = Regex::new(r"^(\S+) -> (\S+):$").unwrap();
+    static ref MEMBER: Regex =
+        Regex::new(r"^(?:(\d+):(\d+):)?\S+ ([^\s(]+)\([^)]*\)(?::(\d+)(?::(\d+))?)? -> (\S+)$")
+            .unwrap();
+    static ref FILE_NAME: Regex = Regex::new(r#""fileName"\s*:\s*"([^"]+)""#).unwrap();
+    static ref FRAME: Regex =
+        Regex::new(r"^(\s*at )([\w$.]+)\.([\w$<>-]+)\(([^:)]*)(?::(\d+))?\)(.*)$").unwrap();
+    static ref HEADER: Regex =
+        Regex::new(r"^(\s*(?:Caused by: |Suppressed: )?)([A-Za-z_$][\w$]*(?:\.[\w$]+)+)(:.*)?$")
+            .unwrap();
+}
+
+///
+/// A method line of a class, like '1:3:void run():10:12 -> a'
+///
+#[derive(Debug, Clone)]
+struct Member {
+    /// The obfuscated lines it covers
+    range: Option<(u32, u32)>,
+    /// Set when the method was inlined from another class
+    class: Option<String>,
+    name: String,
+    /// The original lines, one line when every obfuscated line maps to it
+    original: Option<(u32, Option<u32>)>,
+}
+
+impl Member {
+    fn covers(&self, line: u32) -> bool {
+        matches!(self.range, Some((start, end)) if start <= line && line <= end)
+    }
+
+    fn line(&self, line: u32) -> u32 {
+        match (self.range, self.original) {
+            // Ranges of the same size map line for line, others only to their start
+            (Some((start, end)), Some((original, Some(original_end))))
+                if original_end.checked_sub(original) == end.checked_sub(start) =>
+            {
+                original + line.saturating_sub(start)
+            }
+            (_, Some((original, _))) => original,
+            _ => line,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Class {
+    name: String,
+    /// From R8's '# {"id":"sourceFile","fileName":"Foo.kt"}'
+    file: Option<String>,
+    /// By obfuscated name, in file order so an inline chain reads innermost first
+    members: HashMap<String, Vec<Member>>,
+}
+
+///
+/// A ProGuard or R8 mapping file, turns obfuscated stack frames back into the source ones
+///
+#[derive(Debug, Clone, Default)]
+pub struct Mapping {
+    /// By obfuscated name
+    classes: HashMap<String, Class>,
+}
+
+#[allow(dead_code)]
+impl Mapping {
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| anyhow!("Failed to read mapping {}: {}", path.display(), e))?;
+        Self::parse(&content).map_err(|e| anyhow!("{}: {}", path.display(), e))
+    }
+
+    pub fn parse(content: &str) -> Result<Self> {
+        let mut mapping = Self::default();
+        let mut class: Option<&mut Class> = None;
+        for (i, line) in content.lines().enumerate() {
+            let trimmed = line.trim();
+            if trimmed.is_empty() {
+                continue;
+            }
+            if let Some(comment) = trimmed.strip_prefix('#') {
+                if let (Some(class), Some(cap)) = (class.as_mut(), FILE_NAME.captures(comment)) {
+                    class.file = Some(cap[1].to_string());
+                }
+                continue;
+            }
+            if !line.starts_with(char::is_whitespace) {
+                let cap = CLASS
+                    .captures(trimmed)
+                    .ok_or_else(|| anyhow!("line {}: expected 'class -> obfuscated:'", i + 1))?;
+                class = Some(
+                    mapping
+                        .classes
+                        .entry(cap[2].to_string())
+                        .or_insert_with(|| Class {
+                            name: cap[1].to_string(),
+                            file: None,
+                            members: HashMap::new(),
+                        }),
+                );
+                continue;
+            }
+            let class = class
+                .as_mut()
+                .ok_or_else(|| anyhow!("line {}: member before any class", i + 1))?;
+            // Fields have no parameter list and never show up in a frame
+            let Some(cap) = MEMBER.captures(trimmed) else {
+                continue;
+            };
+            let number = |n: usize| cap.get(n).and_then(|m| m.as_str().parse::<u32>().ok());
+            let (qualified, name) = match cap[3].rsplit_once('.') {
+                Some((owner, name)) => (Some(owner.to_string()), name.to_string()),
+                None => (None, cap[3].to_string()),
+            };
+            let member = Member {
+                range: number(1).zip(number(2)),
+                class: qualified,
+                name,
+                original: number(4).map(|start| (start, number(5))),
+            };
+            class
+                .members
+                .entry(cap[6].to_string())
+                .or_default()
+                .push(member);
+        }
+        Ok(mapping)
+    }
+
+    ///
+    /// Retrace every frame and exception name in a message, an inlined frame becomes
+    /// one line per method it was inlined from
+    ///
+    pub fn retrace(&self, message: &str) -> Option<String> {
+        let mut changed = false;
+        let lines = message
+            .lines()
+            .map(|line| match self.retrace_line(line) {
+                Some(retraced) => {
+                    changed = true;
+                    retraced
+                }
+                None => line.to_string(),
+            })
+            .collect::<Vec<String>>();
+        changed.then(|| lines.join("\n"))
+    }
+
+    fn retrace_line(&self, line: &str) -> Option<String> {
+        if let Some(cap) = FRAME.captures(line) {
+            return self.retrace_frame(&cap);
+        }
+        let cap = HEADER.captures(line)?;
+        let class = self.classes.get(&cap[2])?;
+        Some(format!(
+            "{}{}{}",
+            &cap[1],
+            class.name,
+            cap.get(3).map_or("", |m| m.as_str())
+        ))
+    }
+
+    fn retrace_frame(&self, cap: &regex::Captures) -> Option<String> {
+        let (indent, obfuscated, method, rest) = (&cap[1], &cap[2], &cap[3], &cap[6]);
+        let class = self.classes.get(obfuscated)?;
+        let line = cap.get(5).and_then(|m| m.as_str().parse::<u32>().ok());
+        let frame = |owner: &str, name: &str, line: Option<u32>| {
+            let file = if owner == class.name {
+                class.file.clone().unwrap_or_else(|| source_file(owner))
+            } else {
+                source_file(owner)
+            };
+            match line {
+                Some(line) => format!("{}{}.{}({}:{}){}", indent, owner, name, file, line, rest),
+                None => format!("{}{}.{}({}){}", indent, owner, name, file, rest),
+            }
+        };
+
+        let members = match class.members.get(method) {
+            Some(members) => members,
+            // Not renamed, or kept out of the mapping
+            None => return Some(frame(&class.name, method, line)),
+        };
+
+        if let Some(line) = line {
+            let chain = members
+                .iter()
+                .filter(|m| m.covers(line))
+                .collect::<Vec<&Member>>();
+            if !chain.is_empty() {
+                let frames = chain
+                    .iter()
+                    .map(|m| {
+                        let owner = m.class.as_deref().unwrap_or(&class.name);
+                        frame(owner, &m.name, Some(m.line(line)))
+                    })
+                    .collect::<Vec<String>>();
+                return Some(frames.join("\n"));
+            }
+        }
+
+        // No line to go by, every method renamed to it is a candidate
+        let mut candidates: Vec<(&str, &str)> = Vec::new();
+        for m in members {
+            let candidate = (m.class.as_deref().unwrap_or(&class.name), m.name.as_str());
+            if !candidates.contains(&candidate) {
+                candidates.push(candidate);
+            }
+        }
+        let frames = candidates
+            .iter()
+            .enumerate()
+            .map(|(i, (owner, name))| {
+                let f = frame(owner, name, line);
+                if i == 0 {
+                    f
+                } else {
+                    // Like retrace, the other candidates follow as alternatives
+                    f.replacen(indent, &indent.replace("at ", "or "), 1)
+                }
+            })
+            .collect::<Vec<String>>();
+        Some(frames.join("\n"))
+    }
+}
+
+///
+/// The usual source file of a class, 'com.foo.Bar$Inner' lives in 'Bar.java'
+///
+fn source_file(class: &str) -> String {
+    let simple = class.rsplit('.').next().unwrap_or(class);
+    let outer = simple.split('$').next().unwrap_or(simple);
+    format!("{}.java", outer)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn retrace(mapping: &str, message: &str) -> Option<String> {
+        Mapping::parse(mapping).unwrap().retrace(message)
+    }
+
+    #[test]
+    fn keeps_lines_without_an_original_range() {
+        let mapping = "com.foo.Main -> a:\n    1:3:void run() -> b\n";
+        assert_eq!(
+            retrace(mapping, "\tat a.b(SourceFile:2)").as_deref(),
+            Some("\tat com.foo.Main.run(Main.java:2)")
+        );
+    }
+
+    #[test]
+    fn maps_every_line_to_a_single_original() {
+        let mapping = "com.foo.Main -> a:\n    1:3:void run():42 -> b\n";
+        assert_eq!(
+            retrace(mapping, "\tat a.b(SourceFile:3)").as_deref(),
+            Some("\tat com.foo.Main.run(Main.java:42)")
+        );
+    }
+
+    #[test]
+    fn maps_ranges_by_their_size() {
+        let mapping =
+            "com.foo.Main -> a:\n    1:3:void run():10:12 -> b\n    4:6:void stop():20:30 -> c\n";
+        // The same size maps line for line
+        assert_eq!(
+            retrace(mapping, "\tat a.b(SourceFile:2)").as_deref(),
+            Some("\tat com.foo.Main.run(Main.java:11)")
+        );
+        // Another size only to its start
+        assert_eq!(
+            retrace(mapping, "\tat a.c(SourceFile:5)").as_deref(),
+            Some("\tat com.foo.Main.stop(Main.java:20)")
+        );
+    }
+
+    #[test]
+    fn expands_an_inline_chain_innermost_first() {
+        let mapping = "\
+com.foo.Main -> a:
+    1:1:int com.foo.Util.parse(java.lang.String):7:7 -> b
+    1:1:void run():15 -> b
+";
+        assert_eq!(
+            retrace(mapping, "\tat a.b(SourceFile:1)").as_deref(),
+            Some("\tat com.foo.Util.parse(Util.java:7)\n\tat com.foo.Main.run(Main.java:15)")
+        );
+    }
+
+    #[test]
+    fn lists_alternatives_without_a_line() {
+        let mapping = "com.foo.Main -> a:\n    void run() -> b\n    void stop() -> b\n";
+        assert_eq!(
+            retrace(mapping, "\tat a.b(Unknown Source)").as_deref(),
+            Some("\tat com.foo.Main.run(Main.java)\n\tor com.foo.Main.stop(Main.java)")
+        );
+    }
+
+    #[test]
+    fn uses_the_r8_source_file() {
+        let mapping = "\
+com.foo.Main -> a:
+# {\"id\":\"sourceFile\",\"fileName\":\"Main.kt\"}
+    1:1:void run():5 -> b
+";
+        assert_eq!(
+            retrace(
+                mapping,
+                "java.lang.IllegalStateException: a\n\tat a.b(SourceFile:1)"
+            )
+            .as_deref(),
+            Some("java.lang.IllegalStateException: a\n\tat com.foo.Main.run(Main.kt:5)")
+        );
+    }
+
+    #[test]
+    fn retraces_exception_names() {
+        let mapping = "com.foo.BadState -> a.a:\n";
+        assert_eq!(
+            retrace(mapping, "Caused by: a.a: oops").as_deref(),
+            Some("Caused by: com.foo.BadState: oops")
+        );
+        assert_eq!(retrace(mapping, "nothing to see"), None);
+    }
+
+    #[test]
+    fn rejects_a_member_before_any_class() {
+        assert!(Mapping::parse("    void run() -> b\n").is_err());
+        assert!(Mapping::parse("not a class\n").is_err());
+    }
+}
@@ -0,0 +1,232 @@
+use std::env;
+use std::io::IsTerminal;
+
+///
+/// A 24-bit color, degraded to what the terminal supports when painted
+///
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub(crate) struct Color(pub u8, pub u8, pub u8);
+
+///
+/// Text attributes used when painting a string
+///
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub(crate) struct Style {
+    pub fg: Option<Color>,
+    pub bg: Option<Color>,
+    pub bold: bool,
+    pub reverse: bool,
+    pub dim: bool,
+}
+
+#[allow(dead_code)]
+impl Style {
+    pub fn fg(color: Color) -> Self {
+        Self {
+            fg: Some(color),
+            ..Default::default()
+        }
+    }
+
+    pub fn on(mut self, color: Color) -> Self {
+        self.bg = Some(color);
+        self
+    }
+
+    pub fn bold(mut self) -> Self {
+        self.bold = true;
+        self
+    }
+
+    pub fn reverse(mut self) -> Self {
+        self.reverse = true;
+        self
+    }
+
+    pub fn dim(mut self) -> Self {
+        self.dim = true;
+        self
+    }
+}
+
+///
+/// How many colors the output can show
+///
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub(crate) enum ColorMode {
+    Never,
+    Ansi16,
+    Ansi256,
+    TrueColor,
+}
+
+/// The xterm defaults for the 16 basic colors
+const ANSI16: [Color; 16] = [
+    Color(0, 0, 0),
+    Color(205, 0, 0),
+    Color(0, 205, 0),
+    Color(205, 205, 0),
+    Color(0, 0, 238),
+    Color(205, 0, 205),
+    Color(0, 205, 205),
+    Color(229, 229, 229),
+    Color(127, 127, 127),
+    Color(255, 0, 0),
+    Color(0, 255, 0),
+    Color(255, 255, 0),
+    Color(92, 92, 255),
+    Color(255, 0, 255),
+    Color(0, 255, 255),
+    Color(255, 255, 255),
+];
+
+/// The channel levels of the 6x6x6 color cube in the 256 palette
+const CUBE: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+impl ColorMode {
+    ///
+    /// Resolve `--color` against the environment.
+    ///
+    /// `auto` colors only a terminal, `NO_COLOR` turns it off and `CLICOLOR_FORCE`
+    /// turns it on. The palette depth comes from `COLORTERM` and `TERM`.
+    ///
+    pub fn new(choice: &str) -> Self {
+        let enabled = match choice {
+            "never" => false,
+            "always" => true,
+            _ => {
+                if env_present("NO_COLOR") {
+                    false
+                } else if env_forced("CLICOLOR_FORCE") {
+                    true
+                } else {
+                    std::io::stdout().is_terminal()
+                }
+            }
+        };
+
+        if !enabled {
+            return ColorMode::Never;
+        }
+
+        let colorterm = env::var("COLORTERM").unwrap_or_default();
+        let term = env::var("TERM").unwrap_or_default();
+        if colorterm == "truecolor" || colorterm == "24bit" {
+            ColorMode::TrueColor
+        } else if term.contains("256color") {
+            ColorMode::Ansi256
+        } else {
+            ColorMode::Ansi16
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        *self != ColorMode::Never
+    }
+
+    ///
+    /// Wrap `s` in the escape codes for `style`
+    ///
+    pub fn paint(&self, s: &str, style: Style) -> String {
+        if !self.is_enabled() {
+            return s.to_string();
+        }
+        let mut codes = Vec::new();
+        if style.bold {
+            codes.push("1".to_string());
+        }
+        if style.dim {
+            codes.push("2".to_string());
+        }
+        if style.reverse {
+            codes.push("7".to_string());
+        }
+        if let Some(fg) = style.fg {
+            codes.push(self.code(fg, false));
+        }
+        if let Some(bg) = style.bg {
+            codes.push(self.code(bg, true));
+        }
+        if codes.is_empty() {
+            return s.to_string();
+        }
+        format!("\x1b[{}m{}\x1b[0m", codes.join(";"), s)
+    }
+
+    ///
+    /// The palette index `c` is drawn with, `None` when it is drawn as is
+    ///
+    #[allow(dead_code)]
+    pub fn index(&self, c: Color) -> Option<u8> {
+        match self {
+            ColorMode::Ansi256 => Some(to_ansi256(c)),
+            ColorMode::Ansi16 => Some(to_ansi16(c)),
+            _ => None,
+        }
+    }
+
+    fn code(&self, c: Color, bg: bool) -> String {
+        match self {
+            ColorMode::TrueColor => {
+                format!("{};2;{};{};{}", if bg { 48 } else { 38 }, c.0, c.1, c.2)
+            }
+            ColorMode::Ansi256 => format!("{};5;{}", if bg { 48 } else { 38 }, to_ansi256(c)),
+            _ => {
+                let i = to_ansi16(c);
+                let base = match (bg, i < 8) {
+                    (false, true) => 30,
+                    (false, false) => 90 - 8,
+                    (true, true) => 40,
+                    (true, false) => 100 - 8,
+                };
+                (base + i).to_string()
+            }
+        }
+    }
+}
+
+///
+/// Set to anything but empty, like no-color.org asks of NO_COLOR
+///
+fn env_present(name: &str) -> bool {
+    matches!(env::var_os(name), Some(v) if !v.is_empty())
+}
+
+///
+/// Set and not '0', CLICOLOR_FORCE=0 turns forcing off
+///
+fn env_forced(name: &str) -> bool {
+    matches!(env::var(name), Ok(v) if !v.is_empty() && v != "0")
+}
+
+fn distance(a: Color, b: Color) -> u32 {
+    let d = |x: u8, y: u8| (x as i32 - y as i32).pow(2) as u32;
+    d(a.0, b.0) + d(a.1, b.1) + d(a.2, b.2)
+}
+
+fn to_ansi16(c: Color) -> u8 {
+    (0..16u8)
+        .min_by_key(|&i| distance(c, ANSI16[i as usize]))
+        .unwrap()
+}
+
+fn to_ansi256(c: Color) -> u8 {
+    let level = |v: u8| {
+        (0..6usize)
+            .min_by_key(|&i| (CUBE[i] as i32 - v as i32).abs())
+            .unwrap()
+    };
+    let (r, g, b) = (level(c.0), level(c.1), level(c.2));
+    let cube = Color(CUBE[r], CUBE[g], CUBE[b]);
+
+    // The grey ramp runs from 8 to 238 in steps of 10
+    let avg = (c.0 as u32 + c.1 as u32 + c.2 as u32) / 3;
+    let step = (avg.saturating_sub(3) / 10).min(23);
+    let grey = (8 + step * 10) as u8;
+
+    if distance(c, Color(grey, grey, grey)) < distance(c, cube) {
+        232 + step as u8
+    } else {
+        (16 + 36 * r + 6 * g + b) as u8
+    }
+}
@@ -0,0 +1,203 @@
+use crate::event::Event;
+use crate::log::Log;
+use crate::sink::Sink;
+use anyhow::Result;
+use async_trait::async_trait;
+use std::collections::VecDeque;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use tokio::fs;
+use tokio::process::Command;
+use tokio::task::JoinHandle;
+
+/// Lines shown before a crash kept for its report
+const CONTEXT: usize = 200;
+
+///
+/// Writes each crash to its own file in a directory, with the lines shown before it
+///
+pub struct CrashSink {
+    dir: PathBuf,
+    device: String,
+    /// Pull the traces of an ANR from the device
+    pull_anr: bool,
+    context: Mutex<VecDeque<String>>,
+    /// Reports waiting on traces from the device
+    pulls: Mutex<Vec<JoinHandle<()>>>,
+}
+
+impl CrashSink {
+    #[allow(dead_code)]
+    pub async fn new(dir: PathBuf, device: String, pull_anr: bool) -> Result<Self> {
+        fs::create_dir_all(&dir).await?;
+        Ok(Self {
+            dir,
+            device,
+            pull_anr,
+            context: Mutex::new(VecDeque::new()),
+            pulls: Mutex::new(Vec::new()),
+        })
+    }
+
+    ///
+    /// A file name that sorts by time, like 'java_1019_120000_com.foo_1234.txt'
+    ///
+    fn path(&self, kind: &str, date: &str, time: &str, name: &str, pid: &str) -> PathBuf {
+        let digits = |s: &str| s.chars().filter(char::is_ascii_digit).collect::<String>();
+        let name = name
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() || c == '.' || c == '-' {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .collect::<String>();
+        let time = digits(time.split('.').next().unwrap_or_default());
+        let stem = format!("{}_{}_{}_{}_{}", kind, digits(date), time, name, pid);
+        let mut path = self.dir.join(format!("{}.txt", stem));
+        // Crashes in the same second
+        let mut n = 1;
+        while path.exists() {
+            n += 1;
+            path = self.dir.join(format!("{}_{}.txt", stem, n));
+        }
+        path
+    }
+
+    fn header(&self, title: String, date: &str, time: &str) -> String {
+        let mut s = format!("{}\nTime: {} {}\n", title, date, time);
+        if !self.device.is_empty() {
+            s.push_str(&format!("Device: {}\n", self.device));
+        }
+        s.push('\n');
+        s
+    }
+}
+
+async fn shell(device: &str, command: &str) -> Option<String> {
+    let mut adb = Command::new("adb");
+    if !device.is_empty() {
+        adb.arg("-s").arg(device);
+    }
+    let output = adb.arg("shell").arg(command).output().await.ok()?;
+    let out = String::from_utf8_lossy(&output.stdout).into_owned();
+    if output.status.success() && !out.trim().is_empty() {
+        Some(out)
+    } else {
+        None
+    }
+}
+
+///
+/// The newest trace in /data/anr when it has the process, which takes root on most
+/// builds, or else what `dumpsys activity lastanr` kept of it
+///
+async fn anr_traces(device: &str, pid: &str) -> Option<(String, String)> {
+    if let Some(newest) = shell(device, "ls -t /data/anr").await {
+        if let Some(file) = newest.lines().map(str::trim).find(|f| !f.is_empty()) {
+            let path = format!("/data/anr/{}", file);
+            if let Some(traces) = shell(device, &format!("cat {}", path)).await {
+                if traces.contains(&format!("----- pid {} at", pid)) {
+                    return Some((path, traces));
+                }
+            }
+        }
+    }
+    let traces = shell(device, "dumpsys activity lastanr").await?;
+    Some(("dumpsys activity lastanr".to_string(), traces))
+}
+
+#[async_trait]
+impl Sink for CrashSink {
+    async fn write(&self, log: Log) {
+        let mut context = self.context.lock().unwrap();
+        context.push_back(log.to_string());
+        if context.len() > CONTEXT {
+            context.pop_front();
+        }
+    }
+
+    async fn event(&self, event: &Event) {
+        let (path, mut content, pull) = match event {
+            Event::JavaCrash(crash) => (
+                self.path("java", &crash.date, &crash.time, &crash.name, &crash.pid),
+                format!(
+                    "{}{}\n",
+                    self.header(crash.to_string(), &crash.date, &crash.time),
+                    crash.lines.join("\n")
+                ),
+                None,
+            ),
+            Event::NativeCrash(crash) => (
+                self.path("native", &crash.date, &crash.time, &crash.name, &crash.pid),
+                format!(
+                    "{}{}\n",
+                    self.header(crash.to_string(), &crash.date, &crash.time),
+                    crash.lines.join("\n")
+                ),
+                None,
+            ),
+            Event::Anr(anr) => (
+                self.path("anr", &anr.date, &anr.time, &anr.name, &anr.pid),
+                format!(
+                    "{}{}\n",
+                    self.header(anr.to_string(), &anr.date, &anr.time),
+                    anr.lines.join("\n")
+                ),
+                self.pull_anr.then(|| anr.pid.clone()),
+            ),
+            _ => return,
+        };
+
+        // The lines before it now, not once the traces are in
+        let context = self
+            .context
+            .lock()
+            .unwrap()
+            .iter()
+            .cloned()
+            .collect::<Vec<String>>();
+        let background = pull.is_some();
+        let device = self.device.clone();
+        let report = {
+            let path = path.clone();
+            async move {
+                if let Some(pid) = pull {
+                    match anr_traces(&device, &pid).await {
+                        Some((source, traces)) => {
+                            content.push_str(&format!("\n--- Traces from {} ---\n", source));
+                            content.push_str(&traces);
+                        }
+                        None => content.push_str("\n--- No traces could be pulled ---\n"),
+                    }
+                }
+                if !context.is_empty() {
+                    content.push_str("\n--- Log before ---\n");
+                    content.push_str(&context.join("\n"));
+                    content.push('\n');
+                }
+                if let Err(e) = fs::write(&path, content).await {
+                    eprintln!("Failed to write {}: {}", path.display(), e);
+                }
+            }
+        };
+        if background {
+            // Pulling takes a few adb round trips, the records keep flowing meanwhile.
+            // The empty file keeps another crash in the same second off the name.
+            let _ = fs::write(&path, "").await;
+            let handle = tokio::spawn(report);
+            self.pulls.lock().unwrap().push(handle);
+        } else {
+            report.await;
+        }
+    }
+
+    async fn finish(&self) {
+        let pulls = std::mem::take(&mut *self.pulls.lock().unwrap());
+        for pull in pulls {
+            let _ = pull.await;
+        }
+    }
+}
@@ -1,41 +1,52 @@
 use crate::log::Log;
+use crate::sink::template::Template;
 use crate::sink::Sink;
 use anyhow::Result;
 use async_trait::async_trait;
 use std::path::PathBuf;
 use tokio::fs::{File, OpenOptions};
 use tokio::io::AsyncWriteExt;
-use tokio::task;
+use tokio::sync::Mutex;
 
 pub struct FileSink {
-    file: File,
+    file: Mutex<File>,
+    template: Template,
 }
 
 impl FileSink {
     #[allow(dead_code)]
-    pub async fn new(file: PathBuf) -> Result<Self> {
+    pub async fn new(file: PathBuf, template: Template) -> Result<Self> {
         let f = OpenOptions::new()
             .write(true)
             .truncate(true)
             .create(true)
             .open(file)
             .await?;
-        Ok(Self { file: f })
+        Ok(Self {
+            file: Mutex::new(f),
+            template,
+        })
+    }
+
+    async fn append(&self, s: String) {
+        // Writes are awaited in order so a bounded capture is complete
+        // by the time the stream ends and the process exits.
+        let mut file = self.file.lock().await;
+        if file.write_all(s.as_bytes()).await.is_ok() {
+            let _ = file.flush().await;
+        }
     }
 }
 
 #[async_trait]
 impl Sink for FileSink {
     async fn write(&self, log: Log) {
-        let s = format!(
-            "{} {:11} {:>5} {:<5} {} {}   {}\n",
-            log.date, log.time, log.pid, log.tid, log.level, log.tag, log.message
-        );
-        let file = self.file.try_clone().await;
-        task::spawn(async move {
-            if let Ok(mut file) = file {
-                if let Ok(_) = file.write_all(s.as_bytes()).await {}
-            }
-        });
+        self.template.resolve(&log);
+        self.append(format!("{}\n", self.template.render(&log)))
+            .await;
+    }
+
+    async fn separator(&self) {
+        self.append("--\n".to_string()).await;
     }
 }
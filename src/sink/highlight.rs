@@ -0,0 +1,67 @@
+use crate::sink::color::Color;
+use crate::sink::theme::parse_color;
+use anyhow::{anyhow, Result};
+use regex::{Regex, RegexBuilder};
+
+///
+/// A pattern drawn highlighted in tags and messages, given as `regex`
+/// or `#rrggbb:regex` to pick the highlight color.
+///
+#[derive(Debug, Clone)]
+pub(crate) struct Highlight {
+    pattern: String,
+    re: Regex,
+    pub color: Option<Color>,
+}
+
+#[allow(dead_code)]
+impl Highlight {
+    pub fn parse(s: &str) -> Result<Self> {
+        let (color, pattern) = match s.split_once(':') {
+            Some((color, pattern)) if color.starts_with('#') && color.len() == 7 => {
+                (Some(parse_color(color).map_err(|e| anyhow!(e))?), pattern)
+            }
+            _ => (None, s),
+        };
+        Ok(Self {
+            pattern: pattern.to_string(),
+            re: Regex::new(pattern)?,
+            color,
+        })
+    }
+
+    pub fn case_insensitive(self) -> Self {
+        let re = RegexBuilder::new(&self.pattern)
+            .case_insensitive(true)
+            .build()
+            .unwrap_or(self.re);
+        Self { re, ..self }
+    }
+}
+
+///
+/// Byte ranges of `s` matched by the rules, in order and not overlapping,
+/// with the rule that matched
+///
+pub(crate) fn spans<'a>(rules: &'a [Highlight], s: &str) -> Vec<(usize, usize, &'a Highlight)> {
+    let mut spans = rules
+        .iter()
+        .flat_map(|rule| {
+            rule.re
+                .find_iter(s)
+                .filter(|m| !m.is_empty())
+                .map(move |m| (m.start(), m.end(), rule))
+        })
+        .collect::<Vec<_>>();
+    spans.sort_by_key(|&(start, end, _)| (start, usize::MAX - end));
+
+    let mut end = 0;
+    spans.retain(|&(s, e, _)| {
+        if s < end {
+            return false;
+        }
+        end = e;
+        true
+    });
+    spans
+}
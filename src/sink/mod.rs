@@ -1,13 +1,35 @@
+mod color;
+mod crash;
 mod file;
+mod highlight;
+mod pretty;
+mod template;
 mod terminal;
+mod theme;
+mod timestamp;
 
+use crate::event::Event;
 use crate::log::Log;
 use async_trait::async_trait;
 
+#[allow(unused_imports)]
+pub(crate) use color::{Color, ColorMode, Style};
+#[allow(unused_imports)]
+pub(crate) use crash::CrashSink;
 #[allow(unused_imports)]
 pub(crate) use file::FileSink;
 #[allow(unused_imports)]
+pub(crate) use highlight::Highlight;
+#[allow(unused_imports)]
+pub(crate) use pretty::Pretty;
+#[allow(unused_imports)]
+pub(crate) use template::{render, Field, Template};
+#[allow(unused_imports)]
 pub(crate) use terminal::TerminalSink;
+#[allow(unused_imports)]
+pub(crate) use theme::{ColorBy, Theme};
+#[allow(unused_imports)]
+pub(crate) use timestamp::{TimeMode, Timestamps};
 
 ///
 /// Sink trait used to develop log out sink
@@ -15,4 +37,24 @@ pub(crate) use terminal::TerminalSink;
 #[async_trait]
 pub trait Sink: Send + Sync {
     async fn write(&self, log: Log);
+
+    /// Show an event noticed in the stream, ignored by default
+    async fn event(&self, _event: &Event) {}
+
+    /// Show a record that was filtered out, or a note about some that were, like grep's
+    /// context lines
+    async fn context(&self, log: Log) {
+        self.write(log).await
+    }
+
+    /// Mark a gap between groups of context lines, ignored by default
+    async fn separator(&self) {}
+
+    /// The sink can take no more output, like a pager that was quit
+    fn closed(&self) -> bool {
+        false
+    }
+
+    /// Wait for output still being written in the background, when the stream ends
+    async fn finish(&self) {}
 }
@@ -0,0 +1,409 @@
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+
+/// Starts of a payload tried per message, past a few it is not one
+const ATTEMPTS: usize = 8;
+
+///
+/// What a part of a pretty printed line is, for its color
+///
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub(crate) enum Token {
+    Key,
+    String,
+    Number,
+    /// true, false and null
+    Literal,
+    Tag,
+    Attr,
+    Comment,
+}
+
+///
+/// A line of a pretty printed message, with the byte ranges of its tokens
+///
+#[derive(Debug, Default)]
+pub(crate) struct Line {
+    pub text: String,
+    pub spans: Vec<(usize, usize, Token)>,
+}
+
+impl Line {
+    fn indented(depth: usize) -> Self {
+        Self {
+            text: "  ".repeat(depth),
+            spans: Vec::new(),
+        }
+    }
+
+    fn push(&mut self, s: &str, token: Option<Token>) {
+        let start = self.text.len();
+        self.text.push_str(s);
+        if let Some(token) = token {
+            self.spans.push((start, self.text.len(), token));
+        }
+    }
+}
+
+///
+/// Finds a JSON or XML payload in a message and lays it out one value or element
+/// per line. Text around the payload stays on lines of its own.
+///
+#[derive(Debug, Clone)]
+pub(crate) struct Pretty {
+    json: bool,
+    xml: bool,
+}
+
+#[allow(dead_code)]
+impl Pretty {
+    ///
+    /// `kind` is 'json', 'xml' or 'all'
+    ///
+    pub fn new(kind: &str) -> Self {
+        Self {
+            json: kind != "xml",
+            xml: kind != "json",
+        }
+    }
+
+    pub fn format(&self, message: &str) -> Option<Vec<Line>> {
+        // The payload and where it sits in the message
+        let find_json = || {
+            message
+                .match_indices(['{', '['])
+                .take(ATTEMPTS)
+                .find_map(|(start, open)| {
+                    let close = if open == "{" { '}' } else { ']' };
+                    let end = message.rfind(close)? + 1;
+                    let payload = &message[start..end];
+                    match serde_json::from_str::<serde_json::Value>(payload) {
+                        Ok(v) if v.is_object() || v.is_array() => Some((start, end, json(payload))),
+                        _ => None,
+                    }
+                })
+        };
+        let find_xml = || {
+            let start = message.find('<')?;
+            let end = message.rfind('>')? + 1;
+            Some((start, end, xml(message.get(start..end)?)?))
+        };
+        let (start, end, mut lines) = self
+            .json
+            .then(find_json)
+            .flatten()
+            .or_else(|| self.xml.then(find_xml).flatten())?;
+
+        let around = |s: &str| {
+            let s = s.trim();
+            (!s.is_empty()).then(|| {
+                let mut line = Line::default();
+                line.push(s, None);
+                line
+            })
+        };
+        lines.extend(around(&message[end..]));
+        if let Some(line) = around(&message[..start]) {
+            lines.insert(0, line);
+        }
+        Some(lines)
+    }
+}
+
+///
+/// Lay out valid JSON, keeping the keys in order and the numbers as written
+///
+fn json(text: &str) -> Vec<Line> {
+    let bytes = text.as_bytes();
+    let mut lines = Vec::new();
+    let mut line = Line::default();
+    let mut depth = 0;
+    let next = |from: usize| {
+        bytes[from..]
+            .iter()
+            .position(|b| !b.is_ascii_whitespace())
+            .map(|i| from + i)
+    };
+    let mut i = 0;
+    while i < bytes.len() {
+        let b = bytes[i];
+        match b {
+            b'{' | b'[' => {
+                let close = if b == b'{' { b'}' } else { b']' };
+                match next(i + 1) {
+                    // Empty, stays on one line
+                    Some(j) if bytes[j] == close => {
+                        line.push(&text[i..i + 1], None);
+                        line.push(&text[j..j + 1], None);
+                        i = j;
+                    }
+                    _ => {
+                        line.push(&text[i..i + 1], None);
+                        depth += 1;
+                        lines.push(std::mem::replace(&mut line, Line::indented(depth)));
+                    }
+                }
+            }
+            b'}' | b']' => {
+                depth -= 1;
+                lines.push(std::mem::replace(&mut line, Line::indented(depth)));
+                line.push(&text[i..i + 1], None);
+            }
+            b',' => {
+                line.push(",", None);
+                lines.push(std::mem::replace(&mut line, Line::indented(depth)));
+            }
+            b':' => line.push(": ", None),
+            b'"' => {
+                let mut j = i + 1;
+                while j < bytes.len() && bytes[j] != b'"' {
+                    j += if bytes[j] == b'\\' { 2 } else { 1 };
+                }
+                let end = (j + 1).min(bytes.len());
+                let key = next(end).is_some_and(|k| bytes[k] == b':');
+                let token = if key { Token::Key } else { Token::String };
+                line.push(&text[i..end], Some(token));
+                i = end;
+                continue;
+            }
+            _ if b.is_ascii_whitespace() => {}
+            _ => {
+                let end = bytes[i..]
+                    .iter()
+                    .position(|b| b",]}: \t\r\n".contains(b))
+                    .map_or(bytes.len(), |n| i + n);
+                let token = if b.is_ascii_alphabetic() {
+                    Token::Literal
+                } else {
+                    Token::Number
+                };
+                line.push(&text[i..end], Some(token));
+                i = end;
+                continue;
+            }
+        }
+        i += 1;
+    }
+    if !line.text.trim().is_empty() {
+        lines.push(line);
+    }
+    lines
+}
+
+enum Item {
+    Open {
+        name: String,
+        attrs: Vec<(String, String)>,
+        empty: bool,
+    },
+    Close(String),
+    Text(String),
+    /// Comments, declarations and the like, as written
+    Other(String),
+}
+
+///
+/// Lay out XML one element per line, an element holding only text keeps it inline.
+/// None when it does not parse or has no element.
+///
+fn xml(text: &str) -> Option<Vec<Line>> {
+    let mut reader = Reader::from_str(text);
+    reader.config_mut().trim_text(true);
+    let open = |e: &BytesStart, empty: bool| {
+        let name = String::from_utf8_lossy(e.name().as_ref()).into_owned();
+        let attrs = e
+            .attributes()
+            .map(|a| {
+                a.ok().map(|a| {
+                    (
+                        String::from_utf8_lossy(a.key.as_ref()).into_owned(),
+                        String::from_utf8_lossy(&a.value).into_owned(),
+                    )
+                })
+            })
+            .collect::<Option<Vec<(String, String)>>>()?;
+        Some(Item::Open { name, attrs, empty })
+    };
+    let mut items = Vec::new();
+    let mut depth: usize = 0;
+    let mut elements = 0;
+    loop {
+        let from = reader.buffer_position() as usize;
+        let event = reader.read_event().ok()?;
+        let raw = || {
+            text.get(from..reader.buffer_position() as usize)
+                .unwrap_or_default()
+                .trim()
+                .to_string()
+        };
+        match event {
+            Event::Start(e) => {
+                depth += 1;
+                elements += 1;
+                items.push(open(&e, false)?);
+            }
+            Event::Empty(e) => {
+                elements += 1;
+                items.push(open(&e, true)?);
+            }
+            Event::End(e) => {
+                depth = depth.checked_sub(1)?;
+                items.push(Item::Close(
+                    String::from_utf8_lossy(e.name().as_ref()).into_owned(),
+                ));
+            }
+            Event::Text(_) => items.push(Item::Text(raw())),
+            Event::Eof => break,
+            _ => items.push(Item::Other(raw())),
+        }
+    }
+    if depth != 0 || elements == 0 {
+        return None;
+    }
+
+    let mut lines = Vec::new();
+    let mut depth = 0;
+    let mut i = 0;
+    while i < items.len() {
+        let mut line = Line::indented(depth);
+        match &items[i] {
+            Item::Open { name, attrs, empty } => {
+                line.push(&format!("<{}", name), Some(Token::Tag));
+                for (key, value) in attrs {
+                    line.push(" ", None);
+                    line.push(key, Some(Token::Attr));
+                    line.push("=", None);
+                    line.push(&format!("\"{}\"", value), Some(Token::String));
+                }
+                if *empty {
+                    line.push("/>", Some(Token::Tag));
+                } else {
+                    line.push(">", Some(Token::Tag));
+                    match (items.get(i + 1), items.get(i + 2)) {
+                        (Some(Item::Text(t)), Some(Item::Close(close))) => {
+                            line.push(t, None);
+                            line.push(&format!("</{}>", close), Some(Token::Tag));
+                            i += 2;
+                        }
+                        (Some(Item::Close(close)), _) => {
+                            line.push(&format!("</{}>", close), Some(Token::Tag));
+                            i += 1;
+                        }
+                        _ => depth += 1,
+                    }
+                }
+            }
+            Item::Close(name) => {
+                depth = depth.saturating_sub(1);
+                line = Line::indented(depth);
+                line.push(&format!("</{}>", name), Some(Token::Tag));
+            }
+            Item::Text(t) => line.push(t, None),
+            Item::Other(s) => line.push(s, Some(Token::Comment)),
+        }
+        lines.push(line);
+        i += 1;
+    }
+    Some(lines)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn texts(lines: Option<Vec<Line>>) -> Option<Vec<String>> {
+        lines.map(|lines| lines.into_iter().map(|l| l.text).collect())
+    }
+
+    #[test]
+    fn lays_out_json() {
+        let lines = Pretty::new("json").format(r#"Response: {"a":1,"b":[true,"x"],"c":{}} done"#);
+        assert_eq!(
+            texts(lines).unwrap(),
+            [
+                "Response:",
+                "{",
+                r#"  "a": 1,"#,
+                r#"  "b": ["#,
+                "    true,",
+                r#"    "x""#,
+                "  ],",
+                r#"  "c": {}"#,
+                "}",
+                "done",
+            ]
+        );
+    }
+
+    #[test]
+    fn marks_json_tokens() {
+        let lines = Pretty::new("json")
+            .format(r#"{"k":"v","n":-1.5e3,"z":null}"#)
+            .unwrap();
+        let tokens = |i: usize| {
+            let line: &Line = &lines[i];
+            line.spans
+                .iter()
+                .map(|&(from, to, token)| (&line.text[from..to], token))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            tokens(1),
+            [(r#""k""#, Token::Key), (r#""v""#, Token::String)]
+        );
+        assert_eq!(
+            tokens(2),
+            [(r#""n""#, Token::Key), ("-1.5e3", Token::Number)]
+        );
+        assert_eq!(
+            tokens(3),
+            [(r#""z""#, Token::Key), ("null", Token::Literal)]
+        );
+    }
+
+    #[test]
+    fn skips_malformed_json() {
+        let pretty = Pretty::new("json");
+        assert!(pretty.format(r#"{"a":1,"b":"#).is_none());
+        assert!(pretty.format("not {json} at all").is_none());
+        assert!(pretty.format("42").is_none());
+        // A bracket before the payload is skipped for the next start
+        assert_eq!(
+            texts(pretty.format(r#"[tag] {"a":[]}"#)).unwrap(),
+            ["[tag]", "{", r#"  "a": []"#, "}"]
+        );
+    }
+
+    #[test]
+    fn lays_out_xml() {
+        let lines = Pretty::new("xml").format(r#"layout <a x="1"><b>t</b><c/><!-- n --></a>"#);
+        assert_eq!(
+            texts(lines).unwrap(),
+            [
+                "layout",
+                r#"<a x="1">"#,
+                "  <b>t</b>",
+                "  <c/>",
+                "  <!-- n -->",
+                "</a>",
+            ]
+        );
+    }
+
+    #[test]
+    fn skips_malformed_xml() {
+        let pretty = Pretty::new("xml");
+        assert!(pretty.format("<a><b></a>").is_none());
+        assert!(pretty.format("<a>").is_none());
+        assert!(pretty.format("x < y > z").is_none());
+        assert!(pretty.format("<!-- only a comment -->").is_none());
+    }
+
+    #[test]
+    fn picks_the_kinds_asked_for() {
+        assert!(Pretty::new("json").format("<a><b/></a>").is_none());
+        assert!(Pretty::new("xml").format(r#"{"a":1}"#).is_none());
+        assert!(Pretty::new("all").format("<a><b/></a>").is_some());
+        assert!(Pretty::new("all").format(r#"{"a":1}"#).is_some());
+    }
+}
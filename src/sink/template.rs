@@ -0,0 +1,437 @@
+use crate::log::Log;
+use crate::process::ProcessTable;
+use anyhow::{anyhow, Result};
+use std::ops::Range;
+use std::sync::Arc;
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+const FIELDS: [&str; 11] = [
+    "date", "time", "pid", "tid", "level", "tag", "msg", "buffer", "process", "thread", "device",
+];
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub(crate) enum Align {
+    Left,
+    Right,
+    Center,
+}
+
+///
+/// A `{name:spec}` placeholder, `spec` is `[<>^][width][.max]`.
+/// A `?` after the name drops the field and the whitespace after it when the value is empty.
+/// `process` is cut from the start rather than the end.
+///
+#[derive(Debug, Clone)]
+pub(crate) struct Field {
+    pub name: String,
+    pub optional: bool,
+    align: Align,
+    width: Option<usize>,
+    max: Option<usize>,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) enum Part {
+    Literal(String),
+    Field(Field),
+}
+
+///
+/// Output line layout shared by the terminal and file sinks, e.g.
+/// `{time} {pid:>5} {tag:20.20} {level} {process?} {msg}`.
+///
+/// Fields are date, time, pid, tid, level, tag, msg, buffer, process, thread and device.
+/// `process` and `thread` are names looked up from the pid and tid, falling back to the numbers.
+/// `{{` and `}}` are literal braces.
+///
+#[derive(Debug, Clone)]
+pub(crate) struct Template {
+    parts: Vec<Part>,
+    defines: Vec<(String, String)>,
+    processes: Option<Arc<ProcessTable>>,
+}
+
+#[allow(dead_code)]
+impl Template {
+    pub fn parse(s: &str) -> Result<Self> {
+        let mut parts = Vec::new();
+        let mut literal = String::new();
+        let mut chars = s.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    literal.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    literal.push('}');
+                }
+                '{' => {
+                    let mut spec = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(c) => spec.push(c),
+                            None => return Err(anyhow!("unclosed '{{' in format")),
+                        }
+                    }
+                    if !literal.is_empty() {
+                        parts.push(Part::Literal(std::mem::take(&mut literal)));
+                    }
+                    parts.push(Part::Field(Field::parse(&spec)?));
+                }
+                '}' => return Err(anyhow!("unmatched '}}' in format")),
+                c => literal.push(c),
+            }
+        }
+        if !literal.is_empty() {
+            parts.push(Part::Literal(literal));
+        }
+        Ok(Self {
+            parts,
+            defines: Vec::new(),
+            processes: None,
+        })
+    }
+
+    ///
+    /// The default terminal layout, showing process and thread names in place
+    /// of the pid and tid when asked to
+    ///
+    pub fn terminal(tag_width: usize, process_names: bool, thread_names: bool) -> Self {
+        let pid = if process_names {
+            "{process:>20.20}"
+        } else {
+            "{pid:>5}"
+        };
+        let tid = if thread_names {
+            "{thread:<15.15}"
+        } else {
+            "{tid:<5}"
+        };
+        Self::parse(&format!(
+            "{{time:11}} {}-{} {{tag:{w}.{w}}} {{level}} {{msg}}",
+            pid,
+            tid,
+            w = tag_width
+        ))
+        .unwrap()
+    }
+
+    /// The default file layout
+    pub fn file() -> Self {
+        Self::parse("{date} {time:11} {pid:>5} {tid:<5} {level} {tag}   {msg}").unwrap()
+    }
+
+    ///
+    /// Give a field a fixed value, like the device serial
+    ///
+    pub fn define(mut self, name: &str, value: &str) -> Self {
+        self.defines.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    ///
+    /// Look up `process` and `thread` names in the table
+    ///
+    pub fn with_processes(mut self, processes: Arc<ProcessTable>) -> Self {
+        self.processes = Some(processes);
+        self
+    }
+
+    fn uses(&self, name: &str) -> bool {
+        self.parts
+            .iter()
+            .any(|p| matches!(p, Part::Field(f) if f.name == name))
+    }
+
+    ///
+    /// Start fetching what the log needs from the device, without waiting for it
+    ///
+    pub fn resolve(&self, log: &Log) {
+        if let Some(processes) = &self.processes {
+            if self.uses("thread") {
+                processes.request_thread_name(&log.pid, &log.tid);
+            }
+        }
+    }
+
+    /// The value of a field for the log, before padding
+    pub fn value(&self, log: &Log, name: &str) -> String {
+        if let Some((_, v)) = self.defines.iter().find(|(n, _)| n == name) {
+            return v.clone();
+        }
+        match name {
+            "date" => log.date.clone(),
+            "time" => log.time.clone(),
+            "pid" => log.pid.clone(),
+            "tid" => log.tid.clone(),
+            "level" => log.level.clone(),
+            "tag" => log.tag.clone(),
+            "msg" => log.message.clone(),
+            "buffer" => log.buffer.clone(),
+            "process" => self
+                .processes
+                .as_ref()
+                .and_then(|p| p.name(&log.pid))
+                .unwrap_or_else(|| log.pid.clone()),
+            "thread" => self
+                .processes
+                .as_ref()
+                .and_then(|p| p.cached_thread_name(&log.pid, &log.tid))
+                .filter(|name| !name.is_empty())
+                .unwrap_or_else(|| log.tid.clone()),
+            _ => String::new(),
+        }
+    }
+
+    ///
+    /// The parts before and after `{msg}`, the tail is empty without one
+    ///
+    pub fn split_msg(&self) -> (&[Part], &[Part]) {
+        match self
+            .parts
+            .iter()
+            .position(|p| matches!(p, Part::Field(f) if f.name == "msg"))
+        {
+            Some(i) => (&self.parts[..i], &self.parts[i + 1..]),
+            None => (&self.parts, &[]),
+        }
+    }
+
+    pub fn msg_field(&self) -> Option<&Field> {
+        self.parts.iter().find_map(|p| match p {
+            Part::Field(f) if f.name == "msg" => Some(f),
+            _ => None,
+        })
+    }
+
+    pub fn has_msg(&self) -> bool {
+        self.split_msg().0.len() < self.parts.len()
+    }
+
+    /// Render the log without styling
+    pub fn render(&self, log: &Log) -> String {
+        render(&self.parts, &|name| self.value(log, name), &|_, s| s)
+    }
+}
+
+///
+/// Render `parts`, `value` gives the text of a field and `paint` gets each padded
+/// field, or each literal when the field is `None`.
+///
+pub(crate) fn render(
+    parts: &[Part],
+    value: &dyn Fn(&str) -> String,
+    paint: &dyn Fn(Option<&Field>, String) -> String,
+) -> String {
+    let mut out = String::new();
+    let mut skip_space = false;
+    for part in parts {
+        match part {
+            Part::Literal(s) => {
+                let s = if skip_space { s.trim_start() } else { s };
+                skip_space = false;
+                if !s.is_empty() {
+                    out.push_str(&paint(None, s.to_string()));
+                }
+            }
+            Part::Field(field) => {
+                let v = value(&field.name);
+                if field.optional && v.is_empty() {
+                    skip_space = true;
+                    continue;
+                }
+                skip_space = false;
+                out.push_str(&paint(Some(field), field.format(&v)));
+            }
+        }
+    }
+    out
+}
+
+impl Field {
+    fn parse(spec: &str) -> Result<Self> {
+        let (name, format) = spec.split_once(':').unwrap_or((spec, ""));
+        let (name, optional) = match name.trim().strip_suffix('?') {
+            Some(name) => (name, true),
+            None => (name.trim(), false),
+        };
+        let name = if name == "message" { "msg" } else { name };
+        if !FIELDS.contains(&name) {
+            return Err(anyhow!(
+                "unknown field '{}', expected one of {}",
+                name,
+                FIELDS.join(", ")
+            ));
+        }
+
+        let (align, format) = match format.chars().next() {
+            Some('<') => (Align::Left, &format[1..]),
+            Some('>') => (Align::Right, &format[1..]),
+            Some('^') => (Align::Center, &format[1..]),
+            _ => (Align::Left, format),
+        };
+        let (width, max) = format.split_once('.').unwrap_or((format, ""));
+        let number = |s: &str| -> Result<Option<usize>> {
+            if s.is_empty() {
+                Ok(None)
+            } else {
+                s.parse::<usize>()
+                    .map(Some)
+                    .map_err(|_| anyhow!("bad format spec '{}' for field '{}'", spec, name))
+            }
+        };
+
+        Ok(Self {
+            name: name.to_string(),
+            optional,
+            align,
+            width: number(width)?,
+            max: number(max)?,
+        })
+    }
+
+    ///
+    /// Truncate `value` to the max columns and pad it to the width
+    ///
+    pub fn format(&self, value: &str) -> String {
+        let (kept, before, after) = self.fit(value);
+        format!(
+            "{}{}{}",
+            " ".repeat(before),
+            &value[kept],
+            " ".repeat(after)
+        )
+    }
+
+    ///
+    /// The bytes of `value` kept under the max columns, with the padding that goes before
+    /// and after them. Process names keep their end, where the `:service` suffix tells
+    /// processes of a package apart.
+    ///
+    pub fn fit(&self, value: &str) -> (Range<usize>, usize, usize) {
+        let mut used = 0;
+        let mut fits = |c: char| {
+            let w = c.width().unwrap_or(0);
+            if matches!(self.max, Some(max) if used + w > max) {
+                return false;
+            }
+            used += w;
+            true
+        };
+        let kept = if self.name == "process" {
+            let start = value
+                .char_indices()
+                .rev()
+                .take_while(|&(_, c)| fits(c))
+                .last()
+                .map_or(value.len(), |(i, _)| i);
+            start..value.len()
+        } else {
+            let end = value
+                .char_indices()
+                .take_while(|&(_, c)| fits(c))
+                .last()
+                .map_or(0, |(i, c)| i + c.len_utf8());
+            0..end
+        };
+        let pad = self
+            .width
+            .unwrap_or(0)
+            .saturating_sub(value[kept.clone()].width());
+        let (before, after) = match self.align {
+            Align::Left => (0, pad),
+            Align::Right => (pad, 0),
+            Align::Center => (pad / 2, pad - pad / 2),
+        };
+        (kept, before, after)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn field(spec: &str) -> Field {
+        Field::parse(spec).unwrap()
+    }
+
+    fn log() -> Log {
+        Log {
+            date: "10-19".to_string(),
+            time: "12:00:00.000".to_string(),
+            pid: "1234".to_string(),
+            tid: "1235".to_string(),
+            level: "D".to_string(),
+            tag: "MyTag".to_string(),
+            message: "hello".to_string(),
+            buffer: "main".to_string(),
+        }
+    }
+
+    #[test]
+    fn parses_fields_and_literals() {
+        let t = Template::parse("{{{pid:>6}}} {message}").unwrap();
+        assert_eq!(t.render(&log()), "{  1234} hello");
+        assert!(t.has_msg());
+        assert!(Template::parse("{time}").unwrap().msg_field().is_none());
+    }
+
+    #[test]
+    fn rejects_bad_templates() {
+        assert!(Template::parse("{nope}").is_err());
+        assert!(Template::parse("{pid").is_err());
+        assert!(Template::parse("pid}").is_err());
+        assert!(Template::parse("{pid:>x}").is_err());
+        assert!(Template::parse("{pid:5.y}").is_err());
+    }
+
+    #[test]
+    fn pads_and_truncates() {
+        assert_eq!(field("tag:<6").format("ab"), "ab    ");
+        assert_eq!(field("tag:>6").format("ab"), "    ab");
+        assert_eq!(field("tag:^6").format("ab"), "  ab  ");
+        assert_eq!(field("tag:4.4").format("abcdefg"), "abcd");
+        assert_eq!(field("tag:.2").format("abc"), "ab");
+        assert_eq!(field("tag").format("abc"), "abc");
+    }
+
+    #[test]
+    fn keeps_the_end_of_process_names() {
+        let f = field("process:>8.8");
+        assert_eq!(f.format("com.example.app:sync"), "app:sync");
+        assert_eq!(f.format("app"), "     app");
+        let (kept, before, after) = f.fit("com.example.app:sync");
+        assert_eq!((kept, before, after), (12..20, 0, 0));
+    }
+
+    #[test]
+    fn measures_wide_characters() {
+        // Each CJK character takes two columns, none is cut in half
+        assert_eq!(field("tag:5.5").format("日本語"), "日本 ");
+        assert_eq!(field("process:5.5").format("日本語"), "本語 ");
+        // Zero width marks stay with their letter
+        assert_eq!(field("tag:.2").format("e\u{301}ab"), "e\u{301}a");
+    }
+
+    #[test]
+    fn drops_empty_optional_fields_with_their_space() {
+        let t = Template::parse("{time} {buffer?} {tag?}: {msg}").unwrap();
+        let mut l = log();
+        assert_eq!(t.render(&l), "12:00:00.000 main MyTag: hello");
+        l.buffer.clear();
+        assert_eq!(t.render(&l), "12:00:00.000 MyTag: hello");
+        l.tag.clear();
+        assert_eq!(t.render(&l), "12:00:00.000 : hello");
+    }
+
+    #[test]
+    fn splits_around_the_message() {
+        let t = Template::parse("{pid} {msg} [{tag}]").unwrap();
+        let (head, tail) = t.split_msg();
+        assert_eq!(render(head, &|n| t.value(&log(), n), &|_, s| s), "1234 ");
+        assert_eq!(render(tail, &|n| t.value(&log(), n), &|_, s| s), " [MyTag]");
+    }
+}
@@ -1,76 +1,263 @@
+use crate::event::Event;
 use crate::log::Log;
+use crate::sink::color::{ColorMode, Style};
+use crate::sink::highlight::{self, Highlight};
+use crate::sink::pretty::{self, Pretty, Token};
+use crate::sink::template::{self, Field, Template};
+use crate::sink::theme::{ColorBy, Theme};
+use crate::sink::timestamp::Timestamps;
 use crate::sink::Sink;
 use async_trait::async_trait;
-use colored::*;
-use lazy_static::lazy_static;
+use std::io::Write;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use terminal_size::{terminal_size, Width};
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
 
-struct Color(u8, u8, u8);
-
-lazy_static! {
-    static ref ERROR: Color = Color(255, 38, 0);
-    static ref VERBOSE: Color = Color(255, 255, 255);
-    static ref INFO: Color = Color(5, 215, 2);
-    static ref WARNING: Color = Color(215, 95, 2);
-    static ref DEBUG: Color = Color(95, 175, 255);
+pub struct TerminalSink {
+    color: ColorMode,
+    theme: Theme,
+    template: Template,
+    timestamps: Timestamps,
+    highlights: Vec<Highlight>,
+    /// Lay out JSON and XML payloads over several lines
+    pretty: Option<Pretty>,
+    color_by: ColorBy,
+    /// Columns of the terminal, 0 when not writing to one
+    width: Arc<AtomicUsize>,
+    out: Mutex<Box<dyn Write + Send>>,
+    /// Set once a write fails, like when the pager was quit
+    closed: AtomicBool,
 }
 
-macro_rules! error {
-    ($s:expr) => {
-        format!("{}", $s.truecolor(ERROR.0, ERROR.1, ERROR.2))
-    };
-}
+impl TerminalSink {
+    ///
+    /// `color_by` is one of 'level', 'tag', 'message' or 'both', telling whether the tag
+    /// column and the message get the level color or a stable color hashed from the tag.
+    /// Lines go to `out`, stdout or a pager.
+    ///
+    #[allow(dead_code)]
+    pub fn new(
+        out: Box<dyn Write + Send>,
+        color: String,
+        template: Template,
+        theme: Theme,
+        color_by: String,
+        timestamps: Timestamps,
+        highlights: Vec<Highlight>,
+    ) -> Self {
+        Self {
+            color: ColorMode::new(&color),
+            theme,
+            template,
+            timestamps,
+            highlights,
+            pretty: None,
+            color_by: ColorBy::new(&color_by),
+            width: watch_width(),
+            out: Mutex::new(out),
+            closed: AtomicBool::new(false),
+        }
+    }
 
-macro_rules! warn {
-    ($s:expr) => {
-        format!("{}", $s.truecolor(WARNING.0, WARNING.1, WARNING.2))
-    };
-}
+    ///
+    /// Lay out the JSON and XML payloads in messages over several lines
+    ///
+    #[allow(dead_code)]
+    pub fn with_pretty(mut self, pretty: Pretty) -> Self {
+        self.pretty = Some(pretty);
+        self
+    }
 
-macro_rules! info {
-    ($s:expr) => {
-        format!("{}", $s.truecolor(INFO.0, INFO.1, INFO.2))
-    };
-}
+    ///
+    /// Write a line, a reader that went away closes the sink instead of panicking
+    ///
+    fn print(&self, line: &str) {
+        if self.closed.load(Ordering::Relaxed) {
+            return;
+        }
+        let mut out = self.out.lock().unwrap();
+        if writeln!(out, "{}", line).and_then(|_| out.flush()).is_err() {
+            self.closed.store(true, Ordering::Relaxed);
+        }
+    }
 
-macro_rules! debug {
-    ($s:expr) => {
-        format!("{}", $s.truecolor(DEBUG.0, DEBUG.1, DEBUG.2))
-    };
-}
+    fn wrap<'a>(&self, line: &'a str, column: usize) -> Vec<&'a str> {
+        let width = self.width.load(Ordering::Relaxed);
+        // Too narrow to be worth it, let the terminal wrap
+        if width < column + 10 {
+            return vec![line];
+        }
+        wrap(line, width - column)
+    }
 
-macro_rules! verbose {
-    ($s:expr) => {
-        format!("{}", $s.truecolor(VERBOSE.0, VERBOSE.1, VERBOSE.2))
-    };
-}
+    fn value(&self, log: &Log, name: &str) -> String {
+        match name {
+            // The level is drawn as a badge
+            "level" => format!(" {} ", log.level),
+            _ => self.template.value(log, name),
+        }
+    }
 
-pub struct TerminalSink {
-    color: bool,
-    tag_width: usize,
-}
+    fn paint(&self, log: &Log, field: Option<&Field>, s: String, dim: bool) -> String {
+        let (fg, bg) = field.map_or((None, None), |f| {
+            self.theme.field(&f.name, log, self.color_by)
+        });
+        let style = fg.map(Style::fg).unwrap_or_default();
+        let style = bg.map_or(style, |bg| style.on(bg));
+        let style = if dim { style.dim() } else { style };
+        match field.map(|f| f.name.as_str()) {
+            Some("tag") | Some("msg") => self.paint_highlighted(&s, style),
+            _ => self.color.paint(&s, style),
+        }
+    }
 
-impl TerminalSink {
-    #[allow(dead_code)]
-    pub fn new(color: String, tag_width: usize) -> Self {
-        Self {
-            color: color == "always" || color == "auto",
-            tag_width,
+    fn mark(&self, rule: &Highlight, style: Style) -> Style {
+        match rule.color.or(self.theme.highlight) {
+            Some(color) => style.on(color).bold(),
+            None => style.reverse().bold(),
         }
     }
 
-    fn format_by_level(&self, level: &str, s: &str) -> String {
-        if self.color {
-            match level {
-                "V" => verbose!(s),
-                "D" => debug!(s),
-                "I" => info!(s),
-                "W" => warn!(s),
-                "E" => error!(s),
-                "F" => error!(s),
-                _ => verbose!(s),
+    ///
+    /// Paint `s`, drawing --highlight matches bold on the rule's color, or reversed
+    ///
+    fn paint_highlighted(&self, s: &str, style: Style) -> String {
+        if !self.color.is_enabled() {
+            return s.to_string();
+        }
+        let mut out = String::new();
+        let mut pos = 0;
+        for (start, end, rule) in highlight::spans(&self.highlights, s) {
+            if start > pos {
+                out.push_str(&self.color.paint(&s[pos..start], style));
             }
+            out.push_str(&self.color.paint(&s[start..end], self.mark(rule, style)));
+            pos = end;
+        }
+        out.push_str(&self.color.paint(&s[pos..], style));
+        out
+    }
+
+    ///
+    /// Paint a wrapped chunk of a pretty printed line by the tokens in it, the rest in
+    /// the message color, with the --highlight matches and the `{msg}` width on top
+    ///
+    fn paint_syntax(&self, log: &Log, line: &pretty::Line, chunk: &str, dim: bool) -> String {
+        let offset = chunk.as_ptr() as usize - line.text.as_ptr() as usize;
+        let (kept, before, after) = self
+            .template
+            .msg_field()
+            .map_or((0..chunk.len(), 0, 0), |f| f.fit(chunk));
+        let text = &chunk[kept.clone()];
+        let offset = offset + kept.start;
+        // Token spans relative to the text
+        let tokens = line
+            .spans
+            .iter()
+            .filter_map(|&(from, to, token)| {
+                let from = from.saturating_sub(offset).min(text.len());
+                let to = to.saturating_sub(offset).min(text.len());
+                (from < to).then_some((from, to, token))
+            })
+            .collect::<Vec<_>>();
+        let highlights = if self.color.is_enabled() {
+            highlight::spans(&self.highlights, text)
         } else {
-            s.to_string()
+            Vec::new()
+        };
+
+        let mut cuts = vec![0, text.len()];
+        for &(from, to, _) in &tokens {
+            cuts.extend([from, to]);
+        }
+        for &(from, to, _) in &highlights {
+            cuts.extend([from, to]);
+        }
+        cuts.sort_unstable();
+        cuts.dedup();
+
+        let mut out = " ".repeat(before);
+        for pair in cuts.windows(2) {
+            let (from, to) = (pair[0], pair[1]);
+            let token = tokens
+                .iter()
+                .find(|&&(start, end, _)| start <= from && to <= end)
+                .map(|&(_, _, token)| token);
+            let style = match token {
+                Some(Token::Key) | Some(Token::Tag) => Style::fg(self.theme.debug),
+                Some(Token::String) => Style::fg(self.theme.info),
+                Some(Token::Number) | Some(Token::Literal) | Some(Token::Attr) => {
+                    Style::fg(self.theme.warning)
+                }
+                Some(Token::Comment) => Style::fg(self.theme.message(log, self.color_by)).dim(),
+                None => Style::fg(self.theme.message(log, self.color_by)),
+            };
+            let style = match highlights
+                .iter()
+                .find(|&&(start, end, _)| start <= from && to <= end)
+            {
+                Some(&(_, _, rule)) => self.mark(rule, style),
+                None => style,
+            };
+            let style = if dim { style.dim() } else { style };
+            out.push_str(&self.color.paint(&text[from..to], style));
+        }
+        out.push_str(&" ".repeat(after));
+        out
+    }
+
+    ///
+    /// Print a record, dimmed when it is context around a match
+    ///
+    async fn show(&self, log: Log, dim: bool) {
+        self.template.resolve(&log);
+        let (head, tail) = self.template.split_msg();
+        // Timestamps keep state between lines, format once per log
+        let time = self.timestamps.format(&log);
+        let value = |name: &str| match name {
+            "time" => time.clone(),
+            _ => self.value(&log, name),
+        };
+        let first = template::render(head, &value, &|f, s| self.paint(&log, f, s, dim));
+        if !self.template.has_msg() {
+            self.print(&first);
+            return;
+        }
+
+        let column = template::render(head, &value, &|_, s| s).width();
+        // Continuation lines leave the columns before the message blank, the badge too
+        let rest = template::render(head, &value, &|_, s| " ".repeat(s.width()));
+        let msg = self.template.msg_field();
+        let message = match self.pretty.as_ref().and_then(|p| p.format(&log.message)) {
+            Some(lines) => lines
+                .iter()
+                .flat_map(|line| {
+                    self.wrap(&line.text, column)
+                        .into_iter()
+                        .map(|chunk| self.paint_syntax(&log, line, chunk, dim))
+                })
+                .collect::<Vec<String>>(),
+            None => log
+                .message
+                .split('\n')
+                .flat_map(|line| self.wrap(line, column))
+                .map(|s| {
+                    let s = msg.map_or(s.to_string(), |f| f.format(s));
+                    self.paint(&log, msg, s, dim)
+                })
+                .collect::<Vec<String>>(),
+        };
+        let last = message.len() - 1;
+        for (i, s) in message.iter().enumerate() {
+            let mut line = if i == 0 { first.clone() } else { rest.clone() };
+            line.push_str(s);
+            if i == last {
+                line.push_str(&template::render(tail, &value, &|f, s| {
+                    self.paint(&log, f, s, dim)
+                }));
+            }
+            self.print(&line);
         }
     }
 }
@@ -78,42 +265,145 @@ impl TerminalSink {
 #[async_trait]
 impl Sink for TerminalSink {
     async fn write(&self, log: Log) {
-        let mut tag = log.tag;
-        if tag.len() > self.tag_width {
-            tag.truncate(self.tag_width);
-        }
-        let message = &log.message.split('\n').collect::<Vec<&str>>();
-        for (i, &s) in message.iter().enumerate() {
-            let s = if i == 0 {
-                let level = format!(" {} ", self.format_by_level(&log.level, &log.level))
-                    .on_truecolor(88, 88, 88);
-                let tag = self.format_by_level(
-                    &log.level,
-                    &format!("{:width$}", tag, width = self.tag_width),
-                );
-                format!(
-                    "{:11} {:>5}-{:<5} {} {} {}",
-                    log.time,
-                    log.pid,
-                    log.tid,
-                    tag,
-                    level,
-                    self.format_by_level(&log.level, s),
-                )
-            } else {
-                let level = log.level.on_truecolor(88, 88, 88);
-                format!(
-                    "{:12} {:>5}-{:<5} {:width$} {:^3} {}",
-                    "",
-                    "",
-                    "",
-                    " ",
-                    level,
-                    self.format_by_level(&log.level, s),
-                    width = self.tag_width
-                )
-            };
-            println!("{}", s);
+        self.show(log, false).await;
+    }
+
+    async fn context(&self, log: Log) {
+        self.show(log, true).await;
+    }
+
+    async fn separator(&self) {
+        self.print(&self.color.paint("--", Style::default().dim()));
+    }
+
+    async fn event(&self, event: &Event) {
+        let message = event.to_string();
+        // Fill the columns before the message so the banner stands out
+        let (head, _) = self.template.split_msg();
+        let width = template::render(
+            head,
+            &|name| match name {
+                "level" => " V ".to_string(),
+                _ => String::new(),
+            },
+            &|_, s| s,
+        )
+        .width()
+        .saturating_sub(1);
+        if self.color.is_enabled() {
+            self.print(&format!(
+                "{} {}",
+                self.color
+                    .paint(&" ".repeat(width), Style::default().on(self.theme.banner)),
+                self.color.paint(&message, Style::default().bold())
+            ));
+        } else {
+            self.print(&format!("{} {}", "=".repeat(width), message));
         }
+        for line in event.details() {
+            self.print(&format!(
+                "{} {}",
+                " ".repeat(width),
+                self.color.paint(&line, Style::fg(self.theme.fatal))
+            ));
+        }
+    }
+
+    fn closed(&self) -> bool {
+        self.closed.load(Ordering::Relaxed)
+    }
+}
+
+fn terminal_width() -> usize {
+    terminal_size().map(|(Width(w), _)| w as usize).unwrap_or(0)
+}
+
+///
+/// Read the terminal width, and keep it up to date on SIGWINCH
+///
+fn watch_width() -> Arc<AtomicUsize> {
+    let width = Arc::new(AtomicUsize::new(terminal_width()));
+    #[cfg(unix)]
+    if width.load(Ordering::Relaxed) > 0 && tokio::runtime::Handle::try_current().is_ok() {
+        use tokio::signal::unix::{signal, SignalKind};
+        let width = width.clone();
+        tokio::spawn(async move {
+            if let Ok(mut winch) = signal(SignalKind::window_change()) {
+                while winch.recv().await.is_some() {
+                    width.store(terminal_width(), Ordering::Relaxed);
+                }
+            }
+        });
+    }
+    width
+}
+
+///
+/// Split `line` into chunks of at most `width` columns, breaking after
+/// whitespace where that doesn't waste more than half the chunk
+///
+fn wrap(line: &str, width: usize) -> Vec<&str> {
+    let mut chunks = Vec::new();
+    let mut rest = line;
+    while rest.width() > width {
+        let mut used = 0;
+        let mut end = 0;
+        let mut space = None;
+        for (i, c) in rest.char_indices() {
+            let w = c.width().unwrap_or(0);
+            if used + w > width {
+                break;
+            }
+            used += w;
+            end = i + c.len_utf8();
+            if c.is_whitespace() {
+                space = Some(end);
+            }
+        }
+        let cut = match space {
+            Some(space) if space > end / 2 => space,
+            // A single character wider than the column still has to go somewhere
+            _ if end == 0 => rest.chars().next().map_or(rest.len(), char::len_utf8),
+            _ => end,
+        };
+        chunks.push(&rest[..cut]);
+        rest = &rest[cut..];
+    }
+    // Nothing left after a character wider than the column
+    if !rest.is_empty() || chunks.is_empty() {
+        chunks.push(rest);
+    }
+    chunks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wraps_at_spaces() {
+        assert_eq!(wrap("aaa bbb ccc", 8), ["aaa bbb ", "ccc"]);
+        assert_eq!(wrap("short", 8), ["short"]);
+        // A space too early in the line is not worth the gap
+        assert_eq!(wrap("a bbbbbbbbbb", 8), ["a bbbbbb", "bbbb"]);
+    }
+
+    #[test]
+    fn wraps_wide_characters_by_columns() {
+        assert_eq!(wrap("日本語テキスト", 6), ["日本語", "テキス", "ト"]);
+        // An odd width leaves the column a wide character does not fit in
+        assert_eq!(wrap("日本語", 5), ["日本", "語"]);
+    }
+
+    #[test]
+    fn keeps_zero_width_characters_with_their_letter() {
+        assert_eq!(wrap("ae\u{301}bc", 2), ["ae\u{301}", "bc"]);
+        assert_eq!(wrap("a\u{200b}b\u{200b}c", 2), ["a\u{200b}b\u{200b}", "c"]);
+    }
+
+    #[test]
+    fn gives_an_over_wide_character_a_line_of_its_own() {
+        assert_eq!(wrap("日x", 1), ["日", "x"]);
+        assert_eq!(wrap("日日", 1), ["日", "日"]);
     }
 }
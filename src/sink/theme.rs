@@ -0,0 +1,276 @@
+use crate::log::Log;
+use crate::sink::color::Color;
+use anyhow::{anyhow, Result};
+use std::path::Path;
+
+///
+/// Colors used by the terminal sink.
+///
+/// A theme file holds `key = value` lines, where the value is `#rrggbb`, or `none`
+/// for the optional colors, or a comma separated list for `palette`.
+/// `base = dark|light|solarized` picks the built-in theme the other keys override.
+///
+/// ```text
+/// base = light
+/// error = #d70000
+/// pid = #878787
+/// palette = #d70000, #008700, #0087d7
+/// ```
+///
+///
+/// What --color-by draws in a stable color per tag rather than the level color
+///
+#[derive(Debug, Default, Copy, Clone)]
+pub(crate) struct ColorBy {
+    pub tag: bool,
+    pub message: bool,
+}
+
+impl ColorBy {
+    /// `color_by` is one of 'level', 'tag', 'message' or 'both'
+    pub fn new(color_by: &str) -> Self {
+        Self {
+            tag: color_by == "tag" || color_by == "both",
+            message: color_by == "message" || color_by == "both",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct Theme {
+    pub verbose: Color,
+    pub debug: Color,
+    pub info: Color,
+    pub warning: Color,
+    pub error: Color,
+    pub fatal: Color,
+    /// Tag column, the level color when unset
+    pub tag: Option<Color>,
+    /// Time and pid-tid columns, left uncolored when unset
+    pub pid: Option<Color>,
+    /// Background of the level badge
+    pub badge: Color,
+    /// Background of process and crash banners
+    pub banner: Color,
+    /// Colors handed out to tags and processes
+    pub palette: Vec<Color>,
+    /// Background of --highlight matches, reverse video when unset
+    pub highlight: Option<Color>,
+}
+
+#[allow(dead_code)]
+impl Theme {
+    pub fn dark() -> Self {
+        Self {
+            verbose: Color(255, 255, 255),
+            debug: Color(95, 175, 255),
+            info: Color(5, 215, 2),
+            warning: Color(215, 95, 2),
+            error: Color(255, 38, 0),
+            fatal: Color(255, 38, 0),
+            tag: None,
+            pid: None,
+            badge: Color(88, 88, 88),
+            banner: Color(229, 229, 229),
+            palette: vec![
+                Color(255, 95, 95),
+                Color(95, 215, 95),
+                Color(255, 215, 95),
+                Color(95, 175, 255),
+                Color(215, 135, 255),
+                Color(95, 215, 215),
+                Color(255, 175, 95),
+                Color(175, 215, 135),
+            ],
+            highlight: None,
+        }
+    }
+
+    pub fn light() -> Self {
+        Self {
+            verbose: Color(58, 58, 58),
+            debug: Color(0, 95, 175),
+            info: Color(0, 135, 0),
+            warning: Color(175, 95, 0),
+            error: Color(215, 0, 0),
+            fatal: Color(175, 0, 95),
+            tag: None,
+            pid: Some(Color(128, 128, 128)),
+            badge: Color(208, 208, 208),
+            banner: Color(58, 58, 58),
+            palette: vec![
+                Color(175, 0, 0),
+                Color(0, 135, 0),
+                Color(175, 135, 0),
+                Color(0, 95, 175),
+                Color(135, 0, 175),
+                Color(0, 135, 135),
+                Color(175, 95, 0),
+                Color(95, 95, 175),
+            ],
+            highlight: None,
+        }
+    }
+
+    pub fn solarized() -> Self {
+        Self {
+            verbose: Color(131, 148, 150),
+            debug: Color(38, 139, 210),
+            info: Color(133, 153, 0),
+            warning: Color(181, 137, 0),
+            error: Color(220, 50, 47),
+            fatal: Color(211, 54, 130),
+            tag: None,
+            pid: Some(Color(88, 110, 117)),
+            badge: Color(7, 54, 66),
+            banner: Color(88, 110, 117),
+            palette: vec![
+                Color(181, 137, 0),
+                Color(203, 75, 22),
+                Color(220, 50, 47),
+                Color(211, 54, 130),
+                Color(108, 113, 196),
+                Color(38, 139, 210),
+                Color(42, 161, 152),
+                Color(133, 153, 0),
+            ],
+            highlight: Some(Color(7, 54, 66)),
+        }
+    }
+
+    fn builtin(name: &str) -> Option<Self> {
+        match name {
+            "dark" => Some(Self::dark()),
+            "light" => Some(Self::light()),
+            "solarized" => Some(Self::solarized()),
+            _ => None,
+        }
+    }
+
+    ///
+    /// Load a built-in theme by name, or a theme file by path
+    ///
+    pub fn load(name: &str) -> Result<Self> {
+        if let Some(theme) = Self::builtin(name) {
+            return Ok(theme);
+        }
+        let content = std::fs::read_to_string(Path::new(name))
+            .map_err(|e| anyhow!("Failed to read theme {}: {}", name, e))?;
+        Self::parse(&content)
+    }
+
+    fn parse(content: &str) -> Result<Self> {
+        let mut theme = Self::dark();
+        for (i, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (key, value) = line
+                .split_once('=')
+                .ok_or_else(|| anyhow!("line {}: expected 'key = value'", i + 1))?;
+            let (key, value) = (key.trim(), value.trim());
+            let err = |e: String| anyhow!("line {}: {}", i + 1, e);
+            match key {
+                "base" => {
+                    theme = Self::builtin(value)
+                        .ok_or_else(|| err(format!("unknown base theme '{}'", value)))?
+                }
+                "verbose" => theme.verbose = parse_color(value).map_err(err)?,
+                "debug" => theme.debug = parse_color(value).map_err(err)?,
+                "info" => theme.info = parse_color(value).map_err(err)?,
+                "warning" => theme.warning = parse_color(value).map_err(err)?,
+                "error" => theme.error = parse_color(value).map_err(err)?,
+                "fatal" => theme.fatal = parse_color(value).map_err(err)?,
+                "tag" => theme.tag = parse_optional(value).map_err(err)?,
+                "pid" => theme.pid = parse_optional(value).map_err(err)?,
+                "badge" => theme.badge = parse_color(value).map_err(err)?,
+                "banner" => theme.banner = parse_color(value).map_err(err)?,
+                "highlight" => theme.highlight = parse_optional(value).map_err(err)?,
+                "palette" => {
+                    theme.palette = value
+                        .split(',')
+                        .map(|c| parse_color(c.trim()))
+                        .collect::<Result<Vec<Color>, String>>()
+                        .map_err(err)?;
+                    if theme.palette.is_empty() {
+                        return Err(err("palette is empty".to_string()));
+                    }
+                }
+                _ => return Err(err(format!("unknown key '{}'", key))),
+            }
+        }
+        Ok(theme)
+    }
+
+    ///
+    /// The foreground and background of a template field of the log, unset ones are
+    /// left uncolored. The terminal and the viewer both draw by it.
+    ///
+    pub fn field(&self, name: &str, log: &Log, by: ColorBy) -> (Option<Color>, Option<Color>) {
+        match name {
+            "time" => (self.pid, None),
+            "pid" | "tid" | "process" if by.tag || by.message => {
+                (Some(self.hashed(&log.pid)), None)
+            }
+            "pid" | "tid" | "process" => (self.pid, None),
+            "tag" if by.tag => (Some(self.hashed(&log.tag)), None),
+            "tag" => (Some(self.tag.unwrap_or(self.level(&log.level))), None),
+            "level" => (Some(self.level(&log.level)), Some(self.badge)),
+            "msg" => (Some(self.message(log, by)), None),
+            _ => (None, None),
+        }
+    }
+
+    pub fn message(&self, log: &Log, by: ColorBy) -> Color {
+        if by.message {
+            self.hashed(&log.tag)
+        } else {
+            self.level(&log.level)
+        }
+    }
+
+    pub fn level(&self, level: &str) -> Color {
+        match level {
+            "D" => self.debug,
+            "I" => self.info,
+            "W" => self.warning,
+            "E" => self.error,
+            "F" => self.fatal,
+            _ => self.verbose,
+        }
+    }
+
+    ///
+    /// A palette color picked by hashing `key`, the same key always gets the same color
+    ///
+    pub fn hashed(&self, key: &str) -> Color {
+        // FNV-1a, stable across runs and platforms
+        let hash = key.bytes().fold(0xcbf29ce484222325u64, |h, b| {
+            (h ^ b as u64).wrapping_mul(0x100000001b3)
+        });
+        self.palette[(hash % self.palette.len() as u64) as usize]
+    }
+}
+
+#[allow(dead_code)]
+pub(crate) fn parse_color(s: &str) -> Result<Color, String> {
+    let hex = s
+        .strip_prefix('#')
+        .filter(|h| h.len() == 6 && h.is_ascii())
+        .ok_or_else(|| format!("expected '#rrggbb', found '{}'", s))?;
+    let channel = |i: usize| {
+        u8::from_str_radix(&hex[i..i + 2], 16)
+            .map_err(|_| format!("expected '#rrggbb', found '{}'", s))
+    };
+    Ok(Color(channel(0)?, channel(2)?, channel(4)?))
+}
+
+#[allow(dead_code)]
+fn parse_optional(s: &str) -> Result<Option<Color>, String> {
+    if s == "none" {
+        Ok(None)
+    } else {
+        parse_color(s).map(Some)
+    }
+}
@@ -0,0 +1,102 @@
+use crate::log::Log;
+use chrono::{Datelike, Duration, Local, NaiveDateTime};
+use regex::Regex;
+use std::sync::Mutex;
+
+///
+/// What the time column shows
+///
+#[allow(dead_code)]
+#[derive(Debug, Clone)]
+pub(crate) enum TimeMode {
+    /// The device time as logged
+    Device,
+    /// Time since the first line shown
+    Relative,
+    /// Time since the previous line shown
+    Delta,
+    /// Time since the last line whose tag or message matches
+    Marker(Regex),
+    /// The device time moved to the host clock by the measured offset
+    Local(Duration),
+}
+
+#[derive(Default)]
+struct State {
+    start: Option<NaiveDateTime>,
+    prev: Option<NaiveDateTime>,
+    marker: Option<NaiveDateTime>,
+}
+
+pub(crate) struct Timestamps {
+    mode: TimeMode,
+    year: i32,
+    state: Mutex<State>,
+}
+
+#[allow(dead_code)]
+impl Timestamps {
+    pub fn new(mode: TimeMode) -> Self {
+        Self {
+            mode,
+            year: Local::now().year(),
+            state: Mutex::new(State::default()),
+        }
+    }
+
+    ///
+    /// The time column for the log, the device time when it can't be parsed
+    ///
+    pub fn format(&self, log: &Log) -> String {
+        if let TimeMode::Device = self.mode {
+            return log.time.clone();
+        }
+        let Some(time) = parse(self.year, &log.date, &log.time) else {
+            return log.time.clone();
+        };
+
+        let mut state = self.state.lock().unwrap();
+        let start = *state.start.get_or_insert(time);
+        let prev = state.prev.replace(time).unwrap_or(time);
+        match &self.mode {
+            TimeMode::Relative => delta(time - start),
+            TimeMode::Delta => delta(time - prev),
+            TimeMode::Marker(re) => {
+                if re.is_match(&log.tag) || re.is_match(&log.message) {
+                    state.marker = Some(time);
+                }
+                match state.marker {
+                    Some(marker) => delta(time - marker),
+                    None => "-".to_string(),
+                }
+            }
+            TimeMode::Local(offset) => (time + *offset).format("%H:%M:%S%.3f").to_string(),
+            TimeMode::Device => log.time.clone(),
+        }
+    }
+}
+
+///
+/// Read logcat's 'MM-DD' and 'hh:mm:ss.mmm', which carry no year
+///
+fn parse(year: i32, date: &str, time: &str) -> Option<NaiveDateTime> {
+    NaiveDateTime::parse_from_str(
+        &format!("{}-{} {}", year, date, time),
+        "%Y-%m-%d %H:%M:%S%.f",
+    )
+    .ok()
+}
+
+fn delta(d: Duration) -> String {
+    let ms = d.num_milliseconds();
+    let sign = if ms < 0 { '-' } else { '+' };
+    let ms = ms.abs();
+    let (h, m, s, ms) = (ms / 3_600_000, ms / 60_000 % 60, ms / 1000 % 60, ms % 1000);
+    if h > 0 {
+        format!("{}{}:{:02}:{:02}.{:03}", sign, h, m, s, ms)
+    } else if m > 0 {
+        format!("{}{}:{:02}.{:03}", sign, m, s, ms)
+    } else {
+        format!("{}{}.{:03}", sign, s, ms)
+    }
+}
@@ -1,24 +1,94 @@
 use super::*;
+use anyhow::{anyhow, Result};
 use async_stream::stream;
 use async_trait::async_trait;
 use regex::Regex;
 use std::collections::HashMap;
 use tokio::io::AsyncBufReadExt;
 use tokio::process::{Child, Command};
+
+///
+/// Where logcat starts reading the ring buffers
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Since {
+    /// The most recent N records
+    Count(usize),
+    /// Records from the given time, 'MM-DD hh:mm:ss.mmm'
+    Time(String),
+}
+
 ///
 /// Used to get adb logs
 ///
 pub struct ADBSource {
     device: Option<String>,
+    dump: bool,
+    since: Option<Since>,
+    /// Keep logcat out of our process group
+    detached: bool,
 }
 
 impl ADBSource {
+    #[allow(dead_code)]
     pub fn new(device: Option<String>) -> Self {
-        Self { device }
+        Self::with_mode(device, false, None)
     }
 
-    async fn spawn_adb_logcat(&self) -> Child {
+    ///
+    /// Create a source that dumps the buffers and exits when `dump` is set,
+    /// otherwise follows them, optionally starting from `since`.
+    ///
+    pub fn with_mode(device: Option<String>, dump: bool, since: Option<Since>) -> Self {
+        Self {
+            device,
+            dump,
+            since,
+            detached: false,
+        }
+    }
+
+    ///
+    /// Run logcat in a process group of its own, so a Ctrl-C meant for a pager
+    /// reading our output does not end the stream
+    ///
+    pub fn detached(mut self) -> Self {
+        self.detached = true;
+        self
+    }
+
+    ///
+    /// Clear (flush) the given buffers on the device and wait for adb to finish.
+    ///
+    pub async fn clear(&self, buffers: &[String]) -> Result<()> {
         let mut command = Command::new("adb");
+        if let Some(device) = &self.device {
+            command.arg("-s").arg(device);
+        }
+        command.arg("logcat");
+        for buffer in buffers {
+            command.arg("-b").arg(buffer);
+        }
+        command.arg("-c");
+        let output = command.output().await?;
+        if output.status.success() {
+            Ok(())
+        } else {
+            Err(anyhow!(
+                "adb logcat -c failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            ))
+        }
+    }
+
+    async fn spawn_adb_logcat(&self) -> Child {
+        let mut adb = std::process::Command::new("adb");
+        #[cfg(unix)]
+        if self.detached {
+            use std::os::unix::process::CommandExt;
+            adb.process_group(0);
+        }
+        let mut command = Command::from(adb);
         command.stdout(std::process::Stdio::piped());
         if let Some(device) = &self.device {
             command.arg("-s").arg(device);
@@ -27,10 +97,46 @@ impl ADBSource {
         command.arg("-D");
         command.arg("-v").arg("long");
         command.arg("-b").arg("all");
+        // -t implies -d, -T keeps following after the initial records
+        let start = if self.dump { "-t" } else { "-T" };
+        match &self.since {
+            Some(Since::Count(count)) => {
+                command.arg(start).arg(count.to_string());
+            }
+            Some(Since::Time(time)) => {
+                command.arg(start).arg(time);
+            }
+            None if self.dump => {
+                command.arg("-d");
+            }
+            None => {}
+        }
+        // Exiting early, like on --until, should not leave logcat running behind us
+        command.kill_on_drop(true);
         command.spawn().expect("Failed to execute adb logcat")
     }
 }
 
+fn take_log(map: &mut HashMap<&str, String>) -> Option<Log> {
+    if !map.contains_key("tag") {
+        return None;
+    }
+    Some(Log {
+        tag: map.remove("tag").unwrap(),
+        date: map.remove("date").unwrap(),
+        time: map.remove("time").unwrap(),
+        pid: map.remove("pid").unwrap(),
+        tid: map.remove("tid").unwrap(),
+        level: map.remove("level").unwrap(),
+        message: map
+            .remove("message")
+            .unwrap_or_default()
+            .trim_end()
+            .to_string(),
+        buffer: map.get("buffer").cloned().unwrap_or_default(),
+    })
+}
+
 #[async_trait]
 impl Source for ADBSource {
     async fn source(&self) -> LogStream {
@@ -38,6 +144,8 @@ impl Source for ADBSource {
         let mut reader = tokio::io::BufReader::new(logcat.stdout.take().unwrap());
 
         let s = stream! {
+            // Owned by the stream, logcat is killed when the stream is dropped
+            let mut logcat = logcat;
             let mut line = String::new();
             let mut map = HashMap::new();
 
@@ -46,7 +154,7 @@ impl Source for ADBSource {
             let match_buffer = |line: &str| -> Option<String> {
                 if line.starts_with("--------- beginning of")
                     || line.starts_with("--------- switch to") {
-                    let spl = line.trim().split_whitespace().collect::<Vec<&str>>();
+                    let spl = line.split_whitespace().collect::<Vec<&str>>();
                     Some(spl[3].to_string())
                 } else {
                     None
@@ -58,33 +166,13 @@ impl Source for ADBSource {
                     break;
                 }
                 if let Some(b) =  match_buffer(&line) {
-                    if map.contains_key("tag") {
-                        let log = Log {
-                            tag: map.remove("tag").unwrap(),
-                            date: map.remove("date").unwrap(),
-                            time: map.remove("time").unwrap(),
-                            pid: map.remove("pid").unwrap(),
-                            tid: map.remove("tid").unwrap(),
-                            level: map.remove("level").unwrap(),
-                            message: map.remove("message").unwrap().trim_end().to_string(),
-                            buffer: map.get("buffer").unwrap().clone(),
-                        };
+                    if let Some(log) = take_log(&mut map) {
                         yield Ok(log);
                     }
 
                     map.insert("buffer", b);
                 } else if let Some(cap) = re.captures(&line) {
-                        if map.contains_key("tag") {
-                            let log = Log {
-                                tag: map.remove("tag").unwrap(),
-                                date: map.remove("date").unwrap(),
-                                time: map.remove("time").unwrap(),
-                                pid: map.remove("pid").unwrap(),
-                                tid: map.remove("tid").unwrap(),
-                                level: map.remove("level").unwrap(),
-                                message: map.remove("message").unwrap().trim_end().to_string(),
-                                buffer: map.get("buffer").unwrap().clone(),
-                            };
+                        if let Some(log) = take_log(&mut map) {
                             yield Ok(log);
                         }
 
@@ -106,6 +194,22 @@ impl Source for ADBSource {
                 }
                 line.clear();
             }
+
+            // The last record has no following header, flush it when logcat exits
+            if let Some(log) = take_log(&mut map) {
+                yield Ok(log);
+            }
+
+            // Like with no device, adb tells why on stderr
+            match logcat.wait().await {
+                Ok(status) if !status.success() => {
+                    yield Err(anyhow!("adb logcat failed, {}", status).into());
+                }
+                Err(e) => {
+                    yield Err(anyhow!("adb logcat failed, {}", e).into());
+                }
+                _ => {}
+            }
         };
 
         return Box::pin(s);
@@ -11,6 +11,6 @@ pub trait Source: Send + Sync {
 
 mod adb;
 
-pub use adb::ADBSource;
+pub use adb::{ADBSource, Since};
 
 mod file;
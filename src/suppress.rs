@@ -0,0 +1,252 @@
+use crate::log::Log;
+use lazy_static::lazy_static;
+use regex::Regex;
+use std::collections::HashMap;
+use std::time::Duration;
+
+lazy_static! {
+    static ref VARIABLE: Regex = Regex::new(r"0[xX][0-9a-fA-F]+|\b[0-9a-fA-F]{8,}\b|\d+").unwrap();
+}
+
+/// How long a live stream stays quiet before the notes still owed are shown
+pub(crate) const QUIET: Duration = Duration::from_secs(1);
+
+struct Repeat {
+    /// The record shown, with its numbers masked
+    key: (String, String, String, String),
+    /// The latest repeat of it
+    last: Option<Log>,
+    count: usize,
+}
+
+#[derive(Default)]
+struct Window {
+    /// The second of the log time it counts, 'hh:mm:ss'
+    second: String,
+    shown: usize,
+    suppressed: usize,
+    /// The latest suppressed record
+    last: Option<Log>,
+}
+
+///
+/// Collapses repeated records into a 'repeated N times' line and limits the records
+/// shown per tag each second, for SDKs that flood the log
+///
+pub(crate) struct Suppressor {
+    dedup: bool,
+    /// Records shown per tag each second
+    rate: Option<usize>,
+    repeat: Option<Repeat>,
+    windows: HashMap<String, Window>,
+    /// Tags with records suppressed in their window
+    limited: Vec<String>,
+}
+
+impl Suppressor {
+    pub fn new(dedup: bool, rate: Option<usize>) -> Self {
+        Self {
+            dedup,
+            rate,
+            repeat: None,
+            windows: HashMap::new(),
+            limited: Vec::new(),
+        }
+    }
+
+    ///
+    /// Whether to show a record, with the notes about suppressed ones to show first
+    ///
+    pub fn check(&mut self, log: &Log) -> (Vec<Log>, bool) {
+        let mut notes = Vec::new();
+        if self.dedup {
+            let key = (
+                log.pid.clone(),
+                log.level.clone(),
+                log.tag.clone(),
+                VARIABLE.replace_all(&log.message, "#").into_owned(),
+            );
+            match &mut self.repeat {
+                Some(repeat) if repeat.key == key => {
+                    repeat.count += 1;
+                    repeat.last = Some(log.clone());
+                    return (notes, false);
+                }
+                _ => {}
+            }
+            notes.extend(self.repeated());
+            self.repeat = Some(Repeat {
+                key,
+                last: None,
+                count: 0,
+            });
+        }
+
+        let Some(rate) = self.rate else {
+            return (notes, true);
+        };
+        let second = log.time.get(..8).unwrap_or(&log.time);
+        // A tag gone quiet still gets its note once the second is over
+        let windows = &mut self.windows;
+        self.limited.retain(|tag| match windows.get_mut(tag) {
+            Some(window) if window.second != second => {
+                notes.extend(suppressed(window));
+                false
+            }
+            _ => true,
+        });
+
+        let window = self.windows.entry(log.tag.clone()).or_default();
+        if window.second != second {
+            *window = Window {
+                second: second.to_string(),
+                ..Default::default()
+            };
+        }
+        if window.shown < rate {
+            window.shown += 1;
+            return (notes, true);
+        }
+        if window.suppressed == 0 {
+            self.limited.push(log.tag.clone());
+        }
+        window.suppressed += 1;
+        window.last = Some(log.clone());
+        (notes, false)
+    }
+
+    ///
+    /// Whether notes are owed for records already suppressed
+    ///
+    pub fn pending(&self) -> bool {
+        matches!(&self.repeat, Some(repeat) if repeat.last.is_some()) || !self.limited.is_empty()
+    }
+
+    ///
+    /// The notes still owed when the stream ends or goes quiet
+    ///
+    pub fn finish(&mut self) -> Vec<Log> {
+        let mut notes = self.repeated().into_iter().collect::<Vec<Log>>();
+        for tag in std::mem::take(&mut self.limited) {
+            if let Some(window) = self.windows.get_mut(&tag) {
+                notes.extend(suppressed(window));
+            }
+        }
+        notes
+    }
+
+    fn repeated(&mut self) -> Option<Log> {
+        let repeat = self.repeat.take()?;
+        let last = repeat.last?;
+        Some(Log {
+            message: format!("... repeated {} times", repeat.count),
+            ..last
+        })
+    }
+}
+
+fn suppressed(window: &mut Window) -> Option<Log> {
+    let last = window.last.take()?;
+    // Counting starts over for records suppressed after the note
+    let count = std::mem::take(&mut window.suppressed);
+    Some(Log {
+        message: format!("... {} records suppressed over the rate limit", count),
+        ..last
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn log(time: &str, tag: &str, message: &str) -> Log {
+        Log {
+            date: "10-19".to_string(),
+            time: time.to_string(),
+            pid: "1234".to_string(),
+            tid: "1234".to_string(),
+            level: "D".to_string(),
+            tag: tag.to_string(),
+            message: message.to_string(),
+            buffer: "main".to_string(),
+        }
+    }
+
+    fn messages(notes: Vec<Log>) -> Vec<String> {
+        notes.into_iter().map(|l| l.message).collect()
+    }
+
+    #[test]
+    fn masks_numbers_and_hex() {
+        let mut s = Suppressor::new(true, None);
+        assert!(s.check(&log("12:00:00.000", "T", "took 12ms at 0x7f3a")).1);
+        let (notes, show) = s.check(&log("12:00:00.001", "T", "took 345ms at 0xdeadbeef"));
+        assert!(notes.is_empty() && !show);
+        let (_, show) = s.check(&log("12:00:00.002", "T", "took 6ms at 3f2a9c01d4e5"));
+        assert!(!show);
+        assert!(s.pending());
+
+        let (notes, show) = s.check(&log("12:00:00.003", "T", "done"));
+        assert!(show);
+        assert_eq!(messages(notes), ["... repeated 2 times"]);
+        assert!(!s.pending());
+    }
+
+    #[test]
+    fn keeps_different_words() {
+        let mut s = Suppressor::new(true, None);
+        assert!(s.check(&log("12:00:00.000", "T", "open a")).1);
+        assert!(s.check(&log("12:00:00.001", "T", "open b")).1);
+        assert!(s.check(&log("12:00:00.002", "Other", "open b")).1);
+        assert!(s.finish().is_empty());
+    }
+
+    #[test]
+    fn rolls_the_window_over_each_second() {
+        let mut s = Suppressor::new(false, Some(2));
+        assert!(s.check(&log("12:00:00.000", "T", "a")).1);
+        assert!(s.check(&log("12:00:00.100", "T", "b")).1);
+        assert!(!s.check(&log("12:00:00.200", "T", "c")).1);
+        assert!(!s.check(&log("12:00:00.900", "T", "d")).1);
+        // Other tags have their own window
+        assert!(s.check(&log("12:00:00.950", "U", "e")).1);
+
+        let (notes, show) = s.check(&log("12:00:01.000", "T", "f"));
+        assert!(show);
+        assert_eq!(
+            messages(notes),
+            ["... 2 records suppressed over the rate limit"]
+        );
+    }
+
+    #[test]
+    fn notes_a_tag_gone_quiet() {
+        let mut s = Suppressor::new(false, Some(1));
+        s.check(&log("12:00:00.000", "T", "a"));
+        s.check(&log("12:00:00.001", "T", "b"));
+        let (notes, show) = s.check(&log("12:00:01.000", "U", "c"));
+        assert!(show);
+        assert_eq!(notes.len(), 1);
+        assert_eq!(notes[0].tag, "T");
+    }
+
+    #[test]
+    fn counts_again_after_a_flush() {
+        let mut s = Suppressor::new(false, Some(1));
+        s.check(&log("12:00:00.000", "T", "a"));
+        s.check(&log("12:00:00.001", "T", "b"));
+        s.check(&log("12:00:00.002", "T", "c"));
+        assert!(s.pending());
+        assert_eq!(
+            messages(s.finish()),
+            ["... 2 records suppressed over the rate limit"]
+        );
+        assert!(!s.pending());
+
+        s.check(&log("12:00:00.003", "T", "d"));
+        assert_eq!(
+            messages(s.finish()),
+            ["... 1 records suppressed over the rate limit"]
+        );
+    }
+}
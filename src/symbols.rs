@@ -0,0 +1,215 @@
+use addr2line::Loader;
+use anyhow::{anyhow, Result};
+use lazy_static::lazy_static;
+use object::{Object, ReadCache};
+use regex::Regex;
+use std::collections::HashMap;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+lazy_static! {
+    static ref FRAME: Regex = Regex::new(r"^(\s*#\d+ pc ([0-9a-fA-F]+)\s+(\S+))(.*)$").unwrap();
+}
+
+///
+/// Unstripped libraries in a directory, turns the pcs of a native backtrace into
+/// functions and source lines like ndk-stack does
+///
+pub struct Symbols {
+    /// By file name, a library can be there for several ABIs
+    by_name: HashMap<String, Vec<PathBuf>>,
+    by_build_id: HashMap<String, PathBuf>,
+    build_ids: HashMap<PathBuf, String>,
+    /// Opened on the first frame in them, None when there is no debug info to read
+    loaders: Mutex<HashMap<PathBuf, Option<Loader>>>,
+}
+
+#[allow(dead_code)]
+impl Symbols {
+    pub fn new(dir: &Path) -> Result<Self> {
+        if !dir.is_dir() {
+            return Err(anyhow!("{} is not a directory", dir.display()));
+        }
+        let mut symbols = Self {
+            by_name: HashMap::new(),
+            by_build_id: HashMap::new(),
+            build_ids: HashMap::new(),
+            loaders: Mutex::new(HashMap::new()),
+        };
+        symbols.index(dir);
+        Ok(symbols)
+    }
+
+    fn index(&mut self, dir: &Path) {
+        let Ok(entries) = std::fs::read_dir(dir) else {
+            return;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.is_dir() {
+                self.index(&path);
+                continue;
+            }
+            let Some(name) = path.file_name().and_then(|n| n.to_str()) else {
+                continue;
+            };
+            if !name.ends_with(".so") && !name.ends_with(".debug") {
+                continue;
+            }
+            if let Some(build_id) = build_id(&path) {
+                self.by_build_id.insert(build_id.clone(), path.clone());
+                self.build_ids.insert(path.clone(), build_id);
+            }
+            let name = name.trim_end_matches(".debug").to_string();
+            self.by_name.entry(name).or_default().push(path);
+        }
+    }
+
+    ///
+    /// The library a frame ran in, a library of the same name with another build id
+    /// was built from other sources and would point at the wrong lines
+    ///
+    fn library(&self, module: &str, build_id: Option<&str>) -> Option<PathBuf> {
+        if let Some(path) = build_id.and_then(|id| self.by_build_id.get(id)) {
+            return Some(path.clone());
+        }
+        // '/data/app/.../base.apk!libfoo.so' when loaded straight from the apk
+        let name = module.rsplit(['/', '!']).next()?;
+        self.by_name
+            .get(name)?
+            .iter()
+            .find(|path| match (build_id, self.build_ids.get(*path)) {
+                (Some(id), Some(other)) => id == other,
+                _ => true,
+            })
+            .cloned()
+    }
+
+    ///
+    /// Symbolize every backtrace frame in a message, the function and line go where
+    /// the logged symbol was, and the functions it was inlined into follow it
+    ///
+    pub fn symbolize(&self, message: &str) -> Option<String> {
+        let mut changed = false;
+        let lines = message
+            .lines()
+            .map(|line| match self.symbolize_frame(line) {
+                Some(symbolized) => {
+                    changed = true;
+                    symbolized
+                }
+                None => line.to_string(),
+            })
+            .collect::<Vec<String>>();
+        changed.then(|| lines.join("\n"))
+    }
+
+    fn symbolize_frame(&self, line: &str) -> Option<String> {
+        let cap = FRAME.captures(line)?;
+        let pc = u64::from_str_radix(&cap[2], 16).ok()?;
+        let groups = groups(&cap[4]);
+        let build_id = groups
+            .iter()
+            .find_map(|g| g.strip_prefix("BuildId: "))
+            .map(str::to_ascii_lowercase);
+        let path = self.library(&cap[3], build_id.as_deref())?;
+
+        let mut loaders = self.loaders.lock().unwrap();
+        let loader = loaders
+            .entry(path.clone())
+            .or_insert_with(|| Loader::new(&path).ok())
+            .as_ref()?;
+        let functions = functions(loader, pc);
+        let (function, callers) = functions.split_first()?;
+
+        // The offset stays, the symbol goes, the build id stays last
+        let mut symbolized = cap[1].to_string();
+        for group in groups.iter().filter(|g| g.starts_with("offset ")) {
+            symbolized.push_str(&format!(" ({})", group));
+        }
+        symbolized.push_str(&format!(" ({})", function));
+        for group in groups.iter().filter(|g| g.starts_with("BuildId: ")) {
+            symbolized.push_str(&format!(" ({})", group));
+        }
+        let indent = &line[..line.len() - line.trim_start().len()];
+        for caller in callers {
+            symbolized.push_str(&format!("\n{}      inlined into {}", indent, caller));
+        }
+        Some(symbolized)
+    }
+}
+
+///
+/// The functions at a pc as 'foo() at foo.cpp:12', innermost first
+///
+fn functions(loader: &Loader, pc: u64) -> Vec<String> {
+    let mut functions = Vec::new();
+    if let Ok(mut frames) = loader.find_frames(pc) {
+        while let Ok(Some(frame)) = frames.next() {
+            let name = frame
+                .function
+                .as_ref()
+                .and_then(|f| f.demangle().ok())
+                .map(|f| f.into_owned())
+                .unwrap_or_else(|| "??".to_string());
+            let location = frame.location.and_then(|l| {
+                let file = l.file?;
+                Some(match l.line {
+                    Some(line) => format!("{}:{}", file, line),
+                    None => file.to_string(),
+                })
+            });
+            functions.push(match location {
+                Some(location) => format!("{} at {}", name, location),
+                None => name,
+            });
+        }
+    }
+    // No debug info, the symbol table still has the name
+    if functions.is_empty() {
+        if let Some(symbol) = loader.find_symbol(pc) {
+            functions.push(addr2line::demangle_auto(symbol.into(), None).into_owned());
+        }
+    }
+    functions
+}
+
+fn build_id(path: &Path) -> Option<String> {
+    let file = File::open(path).ok()?;
+    let cache = ReadCache::new(file);
+    let object = object::File::parse(&cache).ok()?;
+    let id = object.build_id().ok()??;
+    Some(id.iter().map(|b| format!("{:02x}", b)).collect())
+}
+
+///
+/// The parenthesized groups after the module, like ['abort+164', 'BuildId: 1a2b'].
+/// C++ symbols have parentheses of their own.
+///
+fn groups(rest: &str) -> Vec<String> {
+    let mut groups = Vec::new();
+    let mut depth = 0;
+    let mut current = String::new();
+    for c in rest.chars() {
+        match c {
+            '(' => {
+                if depth > 0 {
+                    current.push(c);
+                }
+                depth += 1;
+            }
+            ')' if depth > 0 => {
+                depth -= 1;
+                if depth == 0 {
+                    groups.push(std::mem::take(&mut current));
+                } else {
+                    current.push(c);
+                }
+            }
+            _ if depth > 0 => current.push(c),
+            _ => {}
+        }
+    }
+    groups
+}
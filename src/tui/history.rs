@@ -0,0 +1,110 @@
+use crate::log::Log;
+use std::collections::VecDeque;
+
+///
+/// A line of the viewer, a log record or an event banner
+///
+#[derive(Debug, Clone)]
+pub(crate) enum Entry {
+    Log {
+        log: Log,
+        /// The process name when the record arrived, pids get reused
+        process: Option<String>,
+    },
+    Event {
+        title: String,
+        details: Vec<String>,
+    },
+}
+
+impl Entry {
+    pub fn is_match(&self, re: &regex::Regex) -> bool {
+        match self {
+            Entry::Log { log, .. } => re.is_match(&log.tag) || re.is_match(&log.message),
+            Entry::Event { title, details } => {
+                re.is_match(title) || details.iter().any(|s| re.is_match(s))
+            }
+        }
+    }
+}
+
+///
+/// The scrollback, every record received with the ones passing the filters marked.
+/// Entries are numbered from the start of the session so the marks survive the
+/// oldest entries being dropped.
+///
+pub(crate) struct History {
+    entries: VecDeque<Entry>,
+    /// Number of the front entry
+    base: u64,
+    capacity: usize,
+    /// Numbers of the entries passing the filters
+    visible: VecDeque<u64>,
+    /// Entries dropped for the capacity
+    pub evicted: u64,
+}
+
+impl History {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            entries: VecDeque::new(),
+            base: 0,
+            capacity,
+            visible: VecDeque::new(),
+            evicted: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn shown(&self) -> usize {
+        self.visible.len()
+    }
+
+    ///
+    /// The `i`th entry passing the filters
+    ///
+    pub fn visible(&self, i: usize) -> &Entry {
+        &self.entries[(self.visible[i] - self.base) as usize]
+    }
+
+    pub fn entries(&self) -> impl Iterator<Item = &Entry> {
+        self.entries.iter()
+    }
+
+    pub fn push(&mut self, entry: Entry, shown: bool) {
+        if shown {
+            self.visible
+                .push_back(self.base + self.entries.len() as u64);
+        }
+        self.entries.push_back(entry);
+        if self.entries.len() > self.capacity {
+            self.entries.pop_front();
+            self.evicted += 1;
+            if self.visible.front() == Some(&self.base) {
+                self.visible.pop_front();
+            }
+            self.base += 1;
+        }
+    }
+
+    ///
+    /// Mark again which entries pass, `shown` holds a flag per entry from the oldest
+    ///
+    pub fn refilter(&mut self, shown: Vec<bool>) {
+        self.visible = shown
+            .into_iter()
+            .enumerate()
+            .filter(|(_, shown)| *shown)
+            .map(|(i, _)| self.base + i as u64)
+            .collect();
+    }
+
+    pub fn clear(&mut self) {
+        self.base += self.entries.len() as u64;
+        self.entries.clear();
+        self.visible.clear();
+    }
+}
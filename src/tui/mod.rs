@@ -0,0 +1,519 @@
+mod history;
+mod view;
+
+use crate::cli::Cli;
+use crate::event::Event;
+use crate::filter::{Filter, LevelFilter, PidFilter, RevertFilter, TagFilter};
+use crate::log::{Level, Log};
+use crate::process::ProcessTable;
+use crate::sink::{ColorBy, ColorMode, Sink, Template, Theme};
+use anyhow::Result;
+use async_trait::async_trait;
+use clap::ValueEnum;
+use history::{Entry, History};
+use ratatui::crossterm::event::{self, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use ratatui::DefaultTerminal;
+use regex::{Regex, RegexBuilder};
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+
+/// Records kept for scrolling back
+const HISTORY: usize = 200_000;
+/// Records waiting for the viewer before new ones are dropped
+const BACKLOG: usize = 50_000;
+
+///
+/// Hands the records and events to the viewer
+///
+pub(crate) struct TuiSink {
+    tx: mpsc::Sender<Entry>,
+    /// For the thread names it needs looked up
+    template: Template,
+    /// Packages that came to the foreground under --follow-focus
+    focus: mpsc::UnboundedSender<String>,
+    processes: Arc<ProcessTable>,
+    dropped: Arc<AtomicU64>,
+}
+
+#[async_trait]
+impl Sink for TuiSink {
+    async fn write(&self, log: Log) {
+        self.template.resolve(&log);
+        let process = self.processes.name(&log.pid);
+        if self.tx.try_send(Entry::Log { log, process }).is_err() {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    async fn event(&self, event: &Event) {
+        if let Event::FocusChanged { package } = event {
+            let _ = self.focus.send(package.clone());
+        }
+        let entry = Entry::Event {
+            title: event.to_string(),
+            details: event.details(),
+        };
+        if self.tx.try_send(entry).is_err() {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+///
+/// Runs the inner filter for its bookkeeping and events but lets every log through,
+/// the viewer applies its own filters
+///
+pub(crate) struct Passthrough<F: Filter>(pub F);
+
+#[async_trait]
+impl<F: Filter> Filter for Passthrough<F> {
+    async fn filter(&self, log: &Log) -> bool {
+        self.0.filter(log).await;
+        false
+    }
+}
+
+///
+/// The filters that can be edited from the viewer
+///
+struct Filters {
+    tag: String,
+    revert: String,
+    level: Level,
+    process: Vec<String>,
+    ignore: bool,
+    chain: Vec<Box<dyn Filter>>,
+}
+
+impl Filters {
+    fn new(tag: String, revert: String, level: Level, process: Vec<String>, ignore: bool) -> Self {
+        let chain: Vec<Box<dyn Filter>> = vec![
+            Box::new(LevelFilter::new(level)),
+            Box::new(TagFilter::new(tag.clone(), ignore)),
+            Box::new(RevertFilter::new(revert.clone(), ignore)),
+        ];
+        Self {
+            tag,
+            revert,
+            level,
+            process,
+            ignore,
+            chain,
+        }
+    }
+
+    fn rebuild(&self) -> Self {
+        Self::new(
+            self.tag.clone(),
+            self.revert.clone(),
+            self.level,
+            self.process.clone(),
+            self.ignore,
+        )
+    }
+
+    async fn shows(&self, entry: &Entry) -> bool {
+        let Entry::Log { log, process } = entry else {
+            return true;
+        };
+        if !self.process.is_empty() {
+            let followed = |s: &str| self.process.iter().any(|p| s.contains(p.as_str()));
+            let matched = match process {
+                Some(name) => followed(name),
+                None => false,
+            } || (log.is_events() && followed(&log.message));
+            if !matched {
+                return false;
+            }
+        }
+        for filter in &self.chain {
+            if filter.filter(log).await {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Prompt {
+    Search,
+    Tag,
+    Revert,
+    Level,
+    Process,
+}
+
+impl Prompt {
+    fn label(&self) -> &'static str {
+        match self {
+            Prompt::Search => "search",
+            Prompt::Tag => "tag",
+            Prompt::Revert => "revert",
+            Prompt::Level => "level",
+            Prompt::Process => "process",
+        }
+    }
+}
+
+///
+/// Full screen viewer over the log stream, with scrollback, pause, search and
+/// filters that can be changed while it runs
+///
+pub(crate) struct Tui {
+    rx: mpsc::Receiver<Entry>,
+    focus: mpsc::UnboundedReceiver<String>,
+    dropped: Arc<AtomicU64>,
+    pid_filter: Arc<PidFilter>,
+    device: String,
+    template: Template,
+    theme: Theme,
+    color: ColorMode,
+    color_by: ColorBy,
+    history: History,
+    /// Entries received while paused
+    pending: VecDeque<Entry>,
+    filters: Filters,
+    search: Option<Regex>,
+    /// The prompt being edited and its text
+    input: Option<(Prompt, String)>,
+    /// Where the scroll was when the search prompt opened
+    search_from: usize,
+    /// A note for the prompt line, like a bad pattern
+    note: Option<String>,
+    paused: bool,
+    /// Shown entries scrolled back from the newest, 0 follows the stream
+    offset: usize,
+    /// Rows of the log view
+    page: usize,
+    rate: u64,
+    rate_count: u64,
+    rate_start: Instant,
+    dirty: bool,
+}
+
+impl Tui {
+    pub fn new(
+        cli: &Cli,
+        template: Template,
+        pid_filter: Arc<PidFilter>,
+        processes: Arc<ProcessTable>,
+    ) -> (TuiSink, Self) {
+        let (tx, rx) = mpsc::channel(BACKLOG);
+        let (focus_tx, focus) = mpsc::unbounded_channel();
+        let dropped = Arc::new(AtomicU64::new(0));
+        let sink = TuiSink {
+            tx,
+            template: template.clone(),
+            focus: focus_tx,
+            processes,
+            dropped: dropped.clone(),
+        };
+        let tui = Self {
+            rx,
+            focus,
+            dropped,
+            pid_filter,
+            device: cli.device.clone(),
+            template,
+            theme: cli.theme.clone(),
+            color: ColorMode::new(&cli.color),
+            color_by: ColorBy::new(&cli.color_by),
+            history: History::new(HISTORY),
+            pending: VecDeque::new(),
+            filters: Filters::new(
+                cli.tag.clone(),
+                cli.revert.clone(),
+                cli.level,
+                cli.process.clone(),
+                cli.ignore,
+            ),
+            search: None,
+            input: None,
+            search_from: 0,
+            note: None,
+            paused: false,
+            offset: 0,
+            page: 0,
+            rate: 0,
+            rate_count: 0,
+            rate_start: Instant::now(),
+            dirty: true,
+        };
+        (sink, tui)
+    }
+
+    ///
+    /// Take over the terminal until the user quits
+    ///
+    pub async fn run(mut self) -> Result<()> {
+        let mut terminal = match ratatui::try_init() {
+            Ok(terminal) => terminal,
+            Err(e) => {
+                // It may have got as far as raw mode
+                ratatui::restore();
+                return Err(e.into());
+            }
+        };
+        let r = self.main_loop(&mut terminal).await;
+        ratatui::restore();
+        r
+    }
+
+    async fn main_loop(&mut self, terminal: &mut DefaultTerminal) -> Result<()> {
+        let mut tick = tokio::time::interval(Duration::from_millis(50));
+        loop {
+            tick.tick().await;
+            self.receive().await;
+            while event::poll(Duration::ZERO)? {
+                match event::read()? {
+                    event::Event::Key(key) if key.kind == KeyEventKind::Press => {
+                        if self.key(key).await {
+                            return Ok(());
+                        }
+                        self.dirty = true;
+                    }
+                    event::Event::Resize(..) => self.dirty = true,
+                    _ => {}
+                }
+            }
+            if self.rate_start.elapsed() >= Duration::from_secs(1) {
+                self.rate = self.rate_count;
+                self.rate_count = 0;
+                self.rate_start = Instant::now();
+                self.dirty = true;
+            }
+            if self.dirty {
+                self.page = terminal.size()?.height.saturating_sub(2) as usize;
+                terminal.draw(|frame| view::draw(frame, self))?;
+                self.dirty = false;
+            }
+        }
+    }
+
+    async fn receive(&mut self) {
+        // The pid filter already switched, the viewer's own filter follows
+        while let Ok(package) = self.focus.try_recv() {
+            self.filters.process = vec![package];
+            self.filters = self.filters.rebuild();
+            self.refilter().await;
+            self.dirty = true;
+        }
+        // Leave time for the keys when the stream is busy
+        for _ in 0..BACKLOG {
+            let Ok(entry) = self.rx.try_recv() else {
+                break;
+            };
+            self.rate_count += 1;
+            if self.paused {
+                self.pending.push_back(entry);
+                if self.pending.len() > HISTORY {
+                    self.pending.pop_front();
+                    self.history.evicted += 1;
+                }
+            } else {
+                self.add(entry).await;
+            }
+            self.dirty = true;
+        }
+    }
+
+    async fn add(&mut self, entry: Entry) {
+        let shown = self.filters.shows(&entry).await;
+        self.history.push(entry, shown);
+        // Keep the view still while scrolled back
+        if shown && self.offset > 0 {
+            self.offset += 1;
+        }
+        self.offset = self.offset.min(self.history.shown().saturating_sub(1));
+    }
+
+    async fn refilter(&mut self) {
+        let mut shown = Vec::with_capacity(self.history.len());
+        for entry in self.history.entries() {
+            shown.push(self.filters.shows(entry).await);
+        }
+        self.history.refilter(shown);
+        self.offset = 0;
+    }
+
+    fn bottom(&self) -> usize {
+        self.history.shown().saturating_sub(1 + self.offset)
+    }
+
+    fn scroll_to(&mut self, i: usize) {
+        self.offset = self.history.shown().saturating_sub(1 + i);
+    }
+
+    ///
+    /// Move to the nearest match before the bottom line, or after it when `newer`
+    ///
+    fn find(&mut self, from: usize, newer: bool) -> bool {
+        let Some(re) = &self.search else {
+            return false;
+        };
+        let found = if newer {
+            (from + 1..self.history.shown()).find(|&i| self.history.visible(i).is_match(re))
+        } else {
+            (0..from)
+                .rev()
+                .find(|&i| self.history.visible(i).is_match(re))
+        };
+        match found {
+            Some(i) => {
+                self.scroll_to(i);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn next_match(&mut self, newer: bool) {
+        if !self.find(self.bottom(), newer) {
+            let which = if newer { "newer" } else { "older" };
+            self.note = Some(format!("No {} match", which));
+        }
+    }
+
+    ///
+    /// Handle a key, returns true to quit
+    ///
+    async fn key(&mut self, key: KeyEvent) -> bool {
+        if key.modifiers.contains(KeyModifiers::CONTROL) && key.code == KeyCode::Char('c') {
+            return true;
+        }
+        if self.input.is_some() {
+            self.edit(key).await;
+            return false;
+        }
+        self.note = None;
+        let page = self.page.max(1);
+        let last = self.history.shown().saturating_sub(1);
+        match key.code {
+            KeyCode::Char('q') => return true,
+            KeyCode::Char(' ') => {
+                self.paused = !self.paused;
+                if !self.paused {
+                    while let Some(entry) = self.pending.pop_front() {
+                        self.add(entry).await;
+                    }
+                }
+            }
+            KeyCode::Up | KeyCode::Char('k') => self.offset = (self.offset + 1).min(last),
+            KeyCode::Down | KeyCode::Char('j') => self.offset = self.offset.saturating_sub(1),
+            KeyCode::PageUp => self.offset = (self.offset + page).min(last),
+            KeyCode::PageDown => self.offset = self.offset.saturating_sub(page),
+            KeyCode::Home | KeyCode::Char('g') => self.offset = last,
+            KeyCode::End | KeyCode::Char('G') => self.offset = 0,
+            KeyCode::Char('n') => self.next_match(false),
+            KeyCode::Char('N') => self.next_match(true),
+            KeyCode::Char('c') => {
+                self.history.clear();
+                self.offset = 0;
+            }
+            KeyCode::Esc => self.search = None,
+            KeyCode::Char('/') => {
+                self.search_from = self.history.shown().saturating_sub(self.offset);
+                self.input = Some((Prompt::Search, String::new()));
+            }
+            KeyCode::Char('t') => self.input = Some((Prompt::Tag, self.filters.tag.clone())),
+            KeyCode::Char('v') => self.input = Some((Prompt::Revert, self.filters.revert.clone())),
+            KeyCode::Char('l') => {
+                self.input = Some((Prompt::Level, format!("{:?}", self.filters.level)))
+            }
+            KeyCode::Char('p') => {
+                self.input = Some((Prompt::Process, self.filters.process.join(" ")))
+            }
+            _ => {}
+        }
+        false
+    }
+
+    async fn edit(&mut self, key: KeyEvent) {
+        let Some((prompt, text)) = &mut self.input else {
+            return;
+        };
+        let prompt = *prompt;
+        match key.code {
+            KeyCode::Esc => {
+                if prompt == Prompt::Search {
+                    self.search = None;
+                    self.scroll_to(self.search_from.saturating_sub(1));
+                }
+                self.input = None;
+                return;
+            }
+            KeyCode::Enter => {
+                let text = text.clone();
+                self.input = None;
+                if let Err(e) = self.apply(prompt, &text).await {
+                    self.note = Some(e);
+                }
+                return;
+            }
+            KeyCode::Backspace => {
+                text.pop();
+            }
+            KeyCode::Char(c) => text.push(c),
+            _ => return,
+        }
+        // Searching moves to the match as the pattern is typed
+        if prompt == Prompt::Search {
+            let text = text.clone();
+            self.search = self.pattern(&text).ok();
+            if !self.find(self.search_from, false) {
+                self.scroll_to(self.search_from.saturating_sub(1));
+            }
+        }
+    }
+
+    fn pattern(&self, s: &str) -> Result<Regex, String> {
+        if s.is_empty() {
+            return Err("empty pattern".to_string());
+        }
+        RegexBuilder::new(s)
+            .case_insensitive(self.filters.ignore)
+            .build()
+            .map_err(|e| e.to_string())
+    }
+
+    async fn apply(&mut self, prompt: Prompt, text: &str) -> Result<(), String> {
+        let text = text.trim();
+        match prompt {
+            Prompt::Search => {
+                self.search = Some(self.pattern(text)?);
+                return Ok(());
+            }
+            Prompt::Tag | Prompt::Revert => {
+                if !text.is_empty() {
+                    self.pattern(text)?;
+                }
+                if prompt == Prompt::Tag {
+                    self.filters.tag = text.to_string();
+                } else {
+                    self.filters.revert = text.to_string();
+                }
+            }
+            Prompt::Level => {
+                self.filters.level = <Level as ValueEnum>::from_str(text, true).map_err(|_| {
+                    format!("unknown level '{}', expected V, D, I, W, E or F", text)
+                })?;
+            }
+            Prompt::Process => {
+                self.filters.process = text
+                    .split(|c: char| c == ',' || c.is_whitespace())
+                    .filter(|s| !s.is_empty())
+                    .map(String::from)
+                    .collect();
+                // Keep the process banners on the new processes
+                self.pid_filter.set_process(self.filters.process.clone());
+            }
+        }
+        self.filters = self.filters.rebuild();
+        self.refilter().await;
+        Ok(())
+    }
+}
@@ -0,0 +1,235 @@
+use crate::log::Log;
+use crate::sink::{self, Color, Field};
+use crate::tui::history::Entry;
+use crate::tui::{Prompt, Tui};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::Paragraph;
+use ratatui::Frame;
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use unicode_width::UnicodeWidthStr;
+
+const HELP: &str =
+    "q quit  space pause  / search  n/N older/newer  t tag  v revert  l level  p process  c clear";
+
+pub(crate) fn draw(frame: &mut Frame, tui: &Tui) {
+    let [logs, status, prompt] = Layout::vertical([
+        Constraint::Min(1),
+        Constraint::Length(1),
+        Constraint::Length(1),
+    ])
+    .areas(frame.area());
+
+    frame.render_widget(Paragraph::new(lines(tui, logs.height as usize)), logs);
+    frame.render_widget(
+        Paragraph::new(status_line(tui)).style(Style::default().add_modifier(Modifier::REVERSED)),
+        status,
+    );
+    frame.render_widget(Paragraph::new(prompt_line(tui)), prompt);
+}
+
+///
+/// The rows ending at the bottom entry, the newest when following
+///
+fn lines(tui: &Tui, height: usize) -> Vec<Line<'static>> {
+    let shown = tui.history.shown();
+    if shown == 0 {
+        return Vec::new();
+    }
+    let mut rows = VecDeque::new();
+    let mut i = tui.bottom() + 1;
+    while i > 0 && rows.len() < height {
+        i -= 1;
+        let lines = match tui.history.visible(i) {
+            Entry::Log { log, .. } => log_lines(tui, log),
+            Entry::Event { title, details } => event_lines(tui, title, details),
+        };
+        for line in lines.into_iter().rev() {
+            rows.push_front(line);
+        }
+    }
+    let skip = rows.len().saturating_sub(height);
+    rows.into_iter().skip(skip).collect()
+}
+
+fn color(tui: &Tui, c: Color) -> Option<ratatui::style::Color> {
+    if !tui.color.is_enabled() {
+        return None;
+    }
+    Some(match tui.color.index(c) {
+        Some(i) => ratatui::style::Color::Indexed(i),
+        None => ratatui::style::Color::Rgb(c.0, c.1, c.2),
+    })
+}
+
+fn fg(tui: &Tui, c: Color) -> Style {
+    match color(tui, c) {
+        Some(c) => Style::default().fg(c),
+        None => Style::default(),
+    }
+}
+
+fn style(tui: &Tui, log: &Log, field: Option<&Field>) -> Style {
+    let (fg, bg) = field.map_or((None, None), |f| {
+        tui.theme.field(&f.name, log, tui.color_by)
+    });
+    let mut style = Style::default();
+    if let Some(fg) = fg.and_then(|c| color(tui, c)) {
+        style = style.fg(fg);
+    }
+    if let Some(bg) = bg.and_then(|c| color(tui, c)) {
+        style = style.bg(bg);
+    }
+    style
+}
+
+///
+/// Split `s` into spans, the search matches drawn reversed
+///
+fn searched(tui: &Tui, s: String, style: Style) -> Vec<Span<'static>> {
+    let Some(re) = &tui.search else {
+        return vec![Span::styled(s, style)];
+    };
+    let mut spans = Vec::new();
+    let mut pos = 0;
+    for m in re.find_iter(&s) {
+        if m.start() > pos {
+            spans.push(Span::styled(s[pos..m.start()].to_string(), style));
+        }
+        spans.push(Span::styled(
+            m.as_str().to_string(),
+            style.add_modifier(Modifier::REVERSED | Modifier::BOLD),
+        ));
+        pos = m.end();
+    }
+    spans.push(Span::styled(s[pos..].to_string(), style));
+    spans
+}
+
+fn log_lines(tui: &Tui, log: &Log) -> Vec<Line<'static>> {
+    let (head, tail) = tui.template.split_msg();
+    let value = |name: &str| match name {
+        "level" => format!(" {} ", log.level),
+        _ => tui.template.value(log, name),
+    };
+    let spans = RefCell::new(Vec::new());
+    let paint = |field: Option<&Field>, s: String| {
+        let style = style(tui, log, field);
+        match field.map(|f| f.name.as_str()) {
+            Some("tag") => spans.borrow_mut().extend(searched(tui, s.clone(), style)),
+            _ => spans.borrow_mut().push(Span::styled(s.clone(), style)),
+        }
+        s
+    };
+    let column = sink::render(head, &value, &paint).width();
+    if !tui.template.has_msg() {
+        return vec![Line::from(spans.into_inner())];
+    }
+
+    let msg = tui.template.msg_field();
+    let msg_style = style(tui, log, msg);
+    let message = log.message.split('\n').collect::<Vec<&str>>();
+    let last = message.len() - 1;
+    let mut lines = Vec::new();
+    for (i, s) in message.iter().enumerate() {
+        let mut line = if i == 0 {
+            spans.take()
+        } else {
+            vec![Span::raw(" ".repeat(column))]
+        };
+        let s = msg.map_or(s.to_string(), |f| f.format(s));
+        line.extend(searched(tui, s, msg_style));
+        if i == last {
+            sink::render(tail, &value, &paint);
+            line.extend(spans.take());
+        }
+        lines.push(Line::from(line));
+    }
+    lines
+}
+
+fn event_lines(tui: &Tui, title: &str, details: &[String]) -> Vec<Line<'static>> {
+    let banner = color(tui, tui.theme.banner).map_or(Style::default(), |c| Style::default().bg(c));
+    let mut spans = vec![Span::styled("   ", banner), Span::raw(" ")];
+    spans.extend(searched(
+        tui,
+        title.to_string(),
+        Style::default().add_modifier(Modifier::BOLD),
+    ));
+    let mut lines = vec![Line::from(spans)];
+    for s in details {
+        let mut spans = vec![Span::raw("    ")];
+        spans.extend(searched(tui, s.clone(), fg(tui, tui.theme.fatal)));
+        lines.push(Line::from(spans));
+    }
+    lines
+}
+
+fn status_line(tui: &Tui) -> Line<'static> {
+    let device = if tui.device.is_empty() {
+        "default device"
+    } else {
+        tui.device.as_str()
+    };
+    let state = if tui.paused {
+        format!("PAUSED +{}", tui.pending.len())
+    } else if tui.offset > 0 {
+        format!("SCROLL -{}", tui.offset)
+    } else {
+        "FOLLOW".to_string()
+    };
+    let filters = &tui.filters;
+    let mut parts = vec![
+        device.to_string(),
+        format!(
+            "{} of {} shown",
+            tui.history.shown(),
+            tui.history.len() + tui.pending.len()
+        ),
+        format!("{}/s", tui.rate),
+        format!(
+            "dropped {}",
+            tui.dropped.load(std::sync::atomic::Ordering::Relaxed)
+        ),
+    ];
+    if tui.history.evicted > 0 {
+        parts.push(format!("evicted {}", tui.history.evicted));
+    }
+    parts.push(format!("level {:?}", filters.level));
+    if !filters.process.is_empty() {
+        parts.push(format!("process {}", filters.process.join(",")));
+    }
+    if !filters.tag.is_empty() {
+        parts.push(format!("tag /{}/", filters.tag));
+    }
+    if !filters.revert.is_empty() {
+        parts.push(format!("revert /{}/", filters.revert));
+    }
+    parts.push(state);
+    Line::from(format!(" {} ", parts.join(" │ ")))
+}
+
+fn prompt_line(tui: &Tui) -> Line<'static> {
+    if let Some((prompt, text)) = &tui.input {
+        let hint = match prompt {
+            Prompt::Level => "  (V D I W E F)",
+            Prompt::Process => "  (names separated by spaces)",
+            _ => "",
+        };
+        return Line::from(vec![
+            Span::styled(
+                format!("{}> ", prompt.label()),
+                Style::default().add_modifier(Modifier::BOLD),
+            ),
+            Span::raw(text.clone()),
+            Span::styled(" ", Style::default().add_modifier(Modifier::REVERSED)),
+            Span::styled(hint, Style::default().add_modifier(Modifier::DIM)),
+        ]);
+    }
+    match &tui.note {
+        Some(note) => Line::from(note.clone()),
+        None => Line::styled(HELP, Style::default().add_modifier(Modifier::DIM)),
+    }
+}